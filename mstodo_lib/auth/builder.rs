@@ -0,0 +1,300 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use super::{
+    presenter::{DeviceCodePresenter, StdoutPresenter},
+    retry::{PendingBackoff, RetryPolicy},
+    store::TokenStore,
+    DeviceCodeAuthentication, PollCallback, PollStatus, API_SCOPES, AUTHORITY_HOST, CLIENT_ID,
+    TENANT,
+};
+use crate::error::{AuthenticationError, Error};
+
+/// Authorities shared by multiple tenants
+/// See "https://learn.microsoft.com/en-us/azure/active-directory/develop/msal-client-application-configuration#authority"
+/// for more information
+const TENANT_ALIASES: &[&str] = &["common", "organizations", "consumers"];
+/// Default of [DeviceCodeAuthenticationBuilder::poll_jitter]
+const DEFAULT_POLL_JITTER: f64 = 0.1;
+
+/// Builds a [DeviceCodeAuthentication] for an app registration other than mstodo's own.
+/// Every value defaults to the one used by mstodo.
+#[derive(Clone)]
+pub struct DeviceCodeAuthenticationBuilder {
+    client_id: String,
+    authority_host: String,
+    tenant: String,
+    scopes: Vec<String>,
+    http_client: Option<reqwest::Client>,
+    default_headers: reqwest::header::HeaderMap,
+    retry_policy: RetryPolicy,
+    poll_jitter: f64,
+    pending_backoff: Option<PendingBackoff>,
+    timeout: Option<Duration>,
+    presenter: Arc<dyn DeviceCodePresenter + Send + Sync>,
+    open_browser: bool,
+    on_poll: Option<PollCallback>,
+    profile: Option<String>,
+    token_store: Option<TokenStore>,
+}
+
+impl Default for DeviceCodeAuthenticationBuilder {
+    fn default() -> Self {
+        Self {
+            client_id: CLIENT_ID.to_owned(),
+            authority_host: AUTHORITY_HOST.to_owned(),
+            tenant: TENANT.to_owned(),
+            scopes: API_SCOPES.iter().map(|s| s.to_string()).collect(),
+            http_client: None,
+            default_headers: crate::utils::default_headers(),
+            retry_policy: RetryPolicy::default(),
+            poll_jitter: DEFAULT_POLL_JITTER,
+            pending_backoff: None,
+            timeout: None,
+            presenter: Arc::new(StdoutPresenter),
+            open_browser: false,
+            on_poll: None,
+            profile: None,
+            token_store: None,
+        }
+    }
+}
+
+impl DeviceCodeAuthenticationBuilder {
+    pub fn client_id(mut self, client_id: &str) -> Self {
+        self.client_id = client_id.to_owned();
+        self
+    }
+
+    /// Identity platform the tokens are requested from,
+    /// e.g. `https://login.microsoftonline.us` for the US Government cloud
+    pub fn authority_host(mut self, authority_host: &str) -> Self {
+        self.authority_host = authority_host.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Either a tenant id or one of `common`, `organizations` and `consumers`
+    pub fn tenant(mut self, tenant: &str) -> Self {
+        self.tenant = tenant.to_owned();
+        self
+    }
+
+    pub fn scopes(mut self, scopes: &[&str]) -> Self {
+        self.scopes = scopes.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Uses a pre-configured client (proxies, TLS, timeouts...) instead of a default one
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Headers added to every request, replacing the defaults with the same name
+    /// such as `User-Agent: mstodo/<version>`
+    pub fn default_headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Fraction of the poll interval by which every wait for authorization
+    /// is randomly lengthened, 10% by default. `0.0` disables it.
+    /// Values are clamped between `0.0` and `1.0`, [Self::build] rejecting
+    /// values that are not finite.
+    pub fn poll_jitter(mut self, poll_jitter: f64) -> Self {
+        self.poll_jitter = poll_jitter;
+        self
+    }
+
+    /// Polls less and less often while the user has not entered the code yet,
+    /// instead of always waiting the interval asked by the server
+    pub fn pending_backoff(mut self, pending_backoff: PendingBackoff) -> Self {
+        self.pending_backoff = Some(pending_backoff);
+        self
+    }
+
+    /// Timeout applied to every request, on top of the client's own
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Replaces the default [StdoutPresenter]
+    pub fn presenter(
+        mut self,
+        presenter: impl DeviceCodePresenter + Send + Sync + 'static,
+    ) -> Self {
+        self.presenter = Arc::new(presenter);
+        self
+    }
+
+    /// Opens the verification page in the default browser after receiving the device code.
+    /// Only has an effect when the `browser` feature is enabled,
+    /// failing to open a browser is logged and never interrupts the flow.
+    pub fn open_browser(mut self, open_browser: bool) -> Self {
+        self.open_browser = open_browser;
+        self
+    }
+
+    /// Called before every poll of the token endpoint, e.g. to drive a spinner or countdown
+    pub fn on_poll(mut self, callback: impl FnMut(PollStatus) + Send + 'static) -> Self {
+        self.on_poll = Some(Arc::new(Mutex::new(callback)));
+        self
+    }
+
+    /// Keeps the token of this account apart from the others, see [TokenStore]
+    pub fn profile(mut self, profile: &str) -> Self {
+        self.profile = Some(profile.to_owned());
+        self
+    }
+
+    /// Replaces the store of the profile, e.g. to keep tokens in another directory
+    pub fn token_store(mut self, token_store: TokenStore) -> Self {
+        self.token_store = Some(token_store);
+        self
+    }
+
+    pub fn build(self) -> Result<DeviceCodeAuthentication, Error> {
+        if !TENANT_ALIASES.contains(&self.tenant.as_str()) && !is_guid(&self.tenant) {
+            return Err(AuthenticationError::InvalidTenant(self.tenant).into());
+        }
+        if !self.poll_jitter.is_finite() {
+            return Err(Error::InvalidInput(format!(
+                "poll jitter must be a finite number, got {}",
+                self.poll_jitter
+            )));
+        }
+        let token_store = match &self.profile {
+            Some(profile) if !is_profile_name(profile) => {
+                return Err(Error::InvalidInput(format!(
+                    "profile names may only contain letters, digits, `-` and `_`, got `{}`",
+                    profile
+                )))
+            }
+            Some(profile) => TokenStore::new(profile),
+            None => TokenStore::default(),
+        };
+        let token_store = self.token_store.unwrap_or(token_store);
+        let authority = format!("{}/{}/oauth2/v2.0", self.authority_host, self.tenant);
+        Ok(DeviceCodeAuthentication {
+            http_client: self.http_client.unwrap_or_default(),
+            default_headers: self.default_headers,
+            retry_policy: self.retry_policy,
+            poll_jitter: self.poll_jitter,
+            pending_backoff: self.pending_backoff,
+            timeout: self.timeout,
+            presenter: self.presenter,
+            open_browser: self.open_browser,
+            on_poll: self.on_poll,
+            token_store,
+            client_id: self.client_id,
+            scope: self.scopes.join(" "),
+            device_code_endpoint: format!("{}/devicecode", authority),
+            token_endpoint: format!("{}/token", authority),
+        })
+    }
+}
+
+/// Whether `s` looks like `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` with hex digits
+fn is_guid(s: &str) -> bool {
+    let groups: Vec<&str> = s.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Profile names end up in file names, so they are kept to a safe subset
+fn is_profile_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tenant_aliases_build_their_endpoints() {
+        for tenant in ["common", "organizations", "consumers"] {
+            let auth = DeviceCodeAuthentication::builder()
+                .tenant(tenant)
+                .build()
+                .unwrap();
+            assert_eq!(
+                auth.device_code_endpoint,
+                format!(
+                    "https://login.microsoftonline.com/{}/oauth2/v2.0/devicecode",
+                    tenant
+                )
+            );
+            assert_eq!(
+                auth.token_endpoint,
+                format!(
+                    "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+                    tenant
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn tenant_id_and_authority_host() {
+        let auth = DeviceCodeAuthentication::builder()
+            .authority_host("https://login.microsoftonline.us/")
+            .tenant("0f6cd6c1-0b2e-4d5b-9c0e-3c5a1d2b4e6f")
+            .build()
+            .unwrap();
+        assert_eq!(
+            auth.token_endpoint,
+            "https://login.microsoftonline.us/0f6cd6c1-0b2e-4d5b-9c0e-3c5a1d2b4e6f/oauth2/v2.0/token"
+        );
+    }
+
+    #[test]
+    fn non_guid_tenant_is_rejected() {
+        for tenant in [
+            "contoso",
+            "0f6cd6c1-0b2e-4d5b-9c0e",
+            "0f6cd6c1-0b2e-4d5b-9c0e-3c5a1d2b4e6g",
+        ] {
+            let result = DeviceCodeAuthentication::builder().tenant(tenant).build();
+            assert!(
+                matches!(
+                    result,
+                    Err(Error::Auth(AuthenticationError::InvalidTenant(ref t))) if t == tenant
+                ),
+                "{}",
+                tenant
+            );
+        }
+    }
+
+    #[test]
+    fn non_finite_poll_jitter_is_rejected() {
+        for jitter in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let result = DeviceCodeAuthentication::builder()
+                .poll_jitter(jitter)
+                .build();
+            assert!(matches!(result, Err(Error::InvalidInput(_))), "{}", jitter);
+        }
+    }
+
+    #[test]
+    fn zero_poll_jitter_is_accepted() {
+        let auth = DeviceCodeAuthentication::builder()
+            .poll_jitter(0.0)
+            .build()
+            .unwrap();
+        assert_eq!(auth.poll_jitter, 0.0);
+    }
+}
@@ -0,0 +1,1686 @@
+//! Only [[DeviceCodeAuthentication]] is implemented.
+//! It takes two steps to authenticate:
+//!     1. Get a device code from the server and wait
+//!         for user to enter the code on the website.
+//!     2. Get an access token from the server.
+//! The access token will be saved in the credential store provided by the OS
+//! when available. otherwise it will be saved in a file under the user's home
+
+const CLIENT_ID: &str = "c85cbdd1-4823-4bc8-b02e-2f3f7caa9dd7";
+const TENANT: &str = "e620629d-ca12-4421-8f81-ba47552f618d";
+/// `openid` and `profile` make the server return an `id_token`
+const API_SCOPES: &[&str] = &[
+    "openid",
+    "profile",
+    "offline_access",
+    "User.Read",
+    "Tasks.ReadWrite",
+];
+const AUTHORITY_HOST: &str = "https://login.microsoftonline.com";
+const SLOW_DOWN_INCREMENT: Duration = Duration::from_secs(5);
+/// Environment variables providing a token obtained elsewhere, e.g. in CI
+const ACCESS_TOKEN_VAR: &str = "MSTODO_ACCESS_TOKEN";
+const REFRESH_TOKEN_VAR: &str = "MSTODO_REFRESH_TOKEN";
+mod builder;
+mod presenter;
+mod provider;
+mod retry;
+pub mod store;
+
+/// Authentication requests
+mod requests {
+    /// Request to get a device code from the server
+    /// The device code will be used to get an access token
+    /// See "https://docs.microsoft.com/en-us/azure/active-directory/develop/v2-oauth2-device-code"
+    /// for more information
+    #[derive(serde::Serialize, Debug, Clone, PartialEq, PartialOrd)]
+    pub(super) struct DeviceCodeAuthenticationRequest<'req> {
+        pub client_id: &'req str,
+
+        pub scope: &'req str,
+    }
+
+    /// Request to get an access token from the server
+    /// The access token will be used to access the API
+    /// See "https://docs.microsoft.com/en-us/azure/active-directory/develop/v2-oauth2-auth-code-flow"
+    /// for more information
+    #[derive(serde::Serialize, Debug, Clone, PartialEq, PartialOrd)]
+    pub(super) struct AuthenticationRequest<'req> {
+        pub client_id: &'req str,
+        pub device_code: &'req str,
+        pub grant_type: &'req str,
+    }
+
+    impl<'req> AuthenticationRequest<'req> {
+        pub fn new(client_id: &'req str, device_code: &'req str) -> Self {
+            Self {
+                client_id,
+                device_code,
+                grant_type: "urn:ietf:params:oauth:grant-type:device_code",
+            }
+        }
+    }
+
+    /// Request to exchange a refresh token for a new access token
+    /// See "https://docs.microsoft.com/en-us/azure/active-directory/develop/v2-oauth2-auth-code-flow#refresh-the-access-token"
+    /// for more information
+    #[derive(serde::Serialize, Debug, Clone, PartialEq, PartialOrd)]
+    pub(super) struct RefreshTokenRequest<'req> {
+        pub client_id: &'req str,
+        pub scope: &'req str,
+        pub refresh_token: &'req str,
+        pub grant_type: &'req str,
+    }
+
+    impl<'req> RefreshTokenRequest<'req> {
+        pub fn new(client_id: &'req str, scope: &'req str, refresh_token: &'req str) -> Self {
+            Self {
+                client_id,
+                scope,
+                refresh_token,
+                grant_type: "refresh_token",
+            }
+        }
+    }
+}
+
+pub mod responses {
+    use std::time::{Duration, SystemTime};
+
+    /// Response from the server when requesting a device code
+    /// The user will need to enter the user code on the website
+    /// and wait for the device code to be authorized.
+    #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, PartialOrd)]
+    pub(super) struct DeviceCodeAuthenticationResponse {
+        pub device_code: String,
+        pub user_code: String,
+        pub verification_uri: String,
+        /// Verification uri with the user code pre-filled, not returned by every tenant
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub verification_uri_complete: Option<String>,
+        pub expires_in: u64,
+        pub interval: u64,
+        pub message: String,
+    }
+
+    /// Error types when requesting an access token
+    /// See "https://docs.microsoft.com/en-us/azure/active-directory/develop/v2-oauth2-auth-code-flow"
+    /// for more information
+    #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum AuthorizationError {
+        AuthorizationPending,
+        AuthorizationDeclined,
+        BadVerificationCode,
+        ExpiredToken,
+        SlowDown,
+        /// The app registration is unknown or not allowed to use the device code flow
+        InvalidClient,
+        /// A requested scope is unknown or not granted to the app
+        InvalidScope,
+        /// Any code unknown to this crate, treated as a failure
+        #[serde(other)]
+        Unknown,
+    }
+
+    /// Error response from the server when requesting an access token
+    /// See "https://docs.microsoft.com/en-us/azure/active-directory/develop/v2-oauth2-auth-code-flow"
+    /// for more information
+    #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+    pub struct DeviceCodeAuthenticationError {
+        pub error: AuthorizationError,
+        pub error_description: String,
+        pub error_codes: Vec<u64>,
+        pub timestamp: String,
+        pub trace_id: String,
+        pub correlation_id: String,
+    }
+
+    #[deprecated(note = "renamed to DeviceCodeAuthenticationError")]
+    pub type DeviceCodeAhenticationError = DeviceCodeAuthenticationError;
+
+    /// Response from the server when requesting an access token
+    /// See "https://docs.microsoft.com/en-us/azure/active-directory/develop/v2-oauth2-auth-code-flow"
+    /// for more information
+    #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+    pub struct AuthenticationResponse {
+        pub token_type: String,
+        pub scope: String,
+        pub expires_in: u64,
+        pub ext_expires_in: u64,
+        pub access_token: String,
+        /// Empty when the server returned none, e.g. without the `offline_access` scope
+        #[serde(default)]
+        pub refresh_token: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub id_token: Option<String>,
+    }
+
+    /// Tokens are redacted, so responses can be logged
+    impl std::fmt::Debug for AuthenticationResponse {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("AuthenticationResponse")
+                .field("token_type", &self.token_type)
+                .field("scope", &self.scope)
+                .field("expires_in", &self.expires_in)
+                .field("ext_expires_in", &self.ext_expires_in)
+                .field("access_token", &super::Redacted(&self.access_token))
+                .field("refresh_token", &super::Redacted(&self.refresh_token))
+                .field("id_token", &self.id_token.as_deref().map(super::Redacted))
+                .finish()
+        }
+    }
+
+    impl AuthenticationResponse {
+        /// Absolute expiry of the access token, `expires_in` being relative
+        /// to when the response was received
+        pub fn expires_at(&self, received_at: SystemTime) -> SystemTime {
+            received_at + Duration::from_secs(self.expires_in)
+        }
+
+        /// Whether the token was granted `scope`, e.g. `Tasks.ReadWrite`.
+        /// Scopes may be listed with their resource prefix,
+        /// e.g. `https://graph.microsoft.com/Tasks.ReadWrite`
+        pub fn has_scope(&self, scope: &str) -> bool {
+            self.scope.split_whitespace().any(|granted| {
+                let name = granted.rsplit('/').next().unwrap_or(granted);
+                name.eq_ignore_ascii_case(scope)
+            })
+        }
+
+        /// Claims about the signed-in user carried by `id_token`, if the server returned one.
+        /// The signature is NOT verified, the claims are only fit for display
+        /// and must not be used to make authorization decisions.
+        pub fn decode_id_token(
+            &self,
+        ) -> Result<Option<IdTokenClaims>, crate::error::AuthenticationError> {
+            use base64::Engine;
+
+            let Some(id_token) = &self.id_token else {
+                return Ok(None);
+            };
+            let invalid = |reason: String| {
+                crate::error::AuthenticationError::UnexpectedResponse(format!(
+                    "invalid id_token: {}",
+                    reason
+                ))
+            };
+            // header.payload.signature
+            let payload = id_token
+                .split('.')
+                .nth(1)
+                .ok_or_else(|| invalid("not a JWT".to_owned()))?;
+            let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(payload.trim_end_matches('='))
+                .map_err(|e| invalid(e.to_string()))?;
+            serde_json::from_slice(&payload)
+                .map(Some)
+                .map_err(|e| invalid(e.to_string()))
+        }
+    }
+
+    /// Claims of an `id_token` identifying the signed-in user
+    /// See "https://learn.microsoft.com/en-us/azure/active-directory/develop/id-token-claims-reference"
+    /// for more information
+    #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+    pub struct IdTokenClaims {
+        /// Usually the email address or phone number used to sign in
+        pub preferred_username: Option<String>,
+        /// Display name, only present with the `profile` scope
+        pub name: Option<String>,
+        /// Object id of the user, the same in every app of the tenant
+        pub oid: Option<String>,
+        /// Tenant the user signed in to
+        pub tid: Option<String>,
+    }
+}
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+pub use builder::DeviceCodeAuthenticationBuilder;
+pub use presenter::{fallback_message, DeviceCodePresenter, StdoutPresenter};
+pub use provider::TokenProvider;
+use responses::*;
+pub use retry::{PendingBackoff, RetryPolicy};
+use store::TokenStore;
+use tokio_util::sync::CancellationToken;
+
+/// Device code returned by [DeviceCodeAuthentication::start_device_code],
+/// to be shown to the user before waiting with [DeviceCodeAuthentication::poll]
+#[derive(Clone)]
+pub struct DeviceCodeHandle {
+    pub user_code: String,
+    pub verification_uri: String,
+    /// Verification uri with the user code pre-filled, not returned by every tenant
+    pub verification_uri_complete: Option<String>,
+    /// Instructions for the user, localized by the server,
+    /// or [fallback_message] when the server sends none
+    pub message: String,
+    /// Lifetime of the code from when it was received
+    pub expires_in: Duration,
+    /// Delay between polls requested by the server
+    pub interval: Duration,
+    device_code: String,
+    deadline: tokio::time::Instant,
+}
+
+impl DeviceCodeHandle {
+    fn new(resp: DeviceCodeAuthenticationResponse) -> Self {
+        let expires_in = Duration::from_secs(resp.expires_in);
+        let message = if resp.message.trim().is_empty() {
+            presenter::fallback_message(&resp.user_code, &resp.verification_uri)
+        } else {
+            resp.message
+        };
+        Self {
+            user_code: resp.user_code,
+            verification_uri: resp.verification_uri,
+            verification_uri_complete: resp.verification_uri_complete,
+            message,
+            expires_in,
+            interval: Duration::from_secs(resp.interval),
+            device_code: resp.device_code,
+            deadline: tokio::time::Instant::now() + expires_in,
+        }
+    }
+
+    /// The uri users should visit, preferring the one with the code pre-filled
+    pub fn verification_link(&self) -> &str {
+        self.verification_uri_complete
+            .as_deref()
+            .unwrap_or(&self.verification_uri)
+    }
+}
+
+/// The device code is a secret, so it is left out
+impl std::fmt::Debug for DeviceCodeHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceCodeHandle")
+            .field("user_code", &self.user_code)
+            .field("verification_uri", &self.verification_uri)
+            .field("verification_uri_complete", &self.verification_uri_complete)
+            .field("message", &self.message)
+            .field("expires_in", &self.expires_in)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Debug-formats a secret as its length only
+pub(crate) struct Redacted<'a>(pub &'a str);
+
+impl std::fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<redacted, {} chars>", self.0.len())
+    }
+}
+
+/// Progress of the device code polling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollStatus {
+    /// Number of polls so far, starting from 1
+    pub attempt: u32,
+    pub interval: Duration,
+    /// Seconds left before the device code expires
+    pub seconds_remaining: u64,
+}
+
+type PollCallback = Arc<Mutex<dyn FnMut(PollStatus) + Send>>;
+
+pub struct DeviceCodeAuthentication {
+    http_client: reqwest::Client,
+    default_headers: reqwest::header::HeaderMap,
+    retry_policy: RetryPolicy,
+    /// See [DeviceCodeAuthenticationBuilder::poll_jitter]
+    poll_jitter: f64,
+    /// See [DeviceCodeAuthenticationBuilder::pending_backoff]
+    pending_backoff: Option<retry::PendingBackoff>,
+    timeout: Option<Duration>,
+    presenter: Arc<dyn DeviceCodePresenter + Send + Sync>,
+    open_browser: bool,
+    on_poll: Option<PollCallback>,
+    token_store: TokenStore,
+    client_id: String,
+    scope: String,
+    device_code_endpoint: String,
+    token_endpoint: String,
+}
+
+impl Default for DeviceCodeAuthentication {
+    fn default() -> Self {
+        Self::builder()
+            .build()
+            .expect("the default tenant is a valid tenant id")
+    }
+}
+
+impl DeviceCodeAuthentication {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_client(client: reqwest::Client) -> Self {
+        Self::builder()
+            .http_client(client)
+            .build()
+            .expect("the default tenant is a valid tenant id")
+    }
+
+    /// Authentication whose token is stored apart from other profiles
+    pub fn for_profile(profile: &str) -> Result<Self, super::error::Error> {
+        Self::builder().profile(profile).build()
+    }
+
+    /// Accounts with a stored token
+    pub fn profiles(&self) -> Vec<String> {
+        self.token_store.profiles()
+    }
+
+    pub fn builder() -> DeviceCodeAuthenticationBuilder {
+        DeviceCodeAuthenticationBuilder::default()
+    }
+
+    /// Returns the stored token when it is still valid, otherwise refreshes it.
+    /// The device code flow is only used when there is nothing to refresh
+    /// or the refresh is rejected.
+    /// A token set in `MSTODO_ACCESS_TOKEN` (and optionally `MSTODO_REFRESH_TOKEN`)
+    /// takes precedence over all of these and is never stored.
+    pub async fn authenticate(&self) -> Result<AuthenticationResponse, super::error::Error> {
+        Ok(self.authenticate_inner(None).await?)
+    }
+
+    /// Same as [Self::authenticate], but the device code polling stops
+    /// with [super::error::AuthenticationError::Cancelled] once `cancel` is cancelled
+    pub async fn authenticate_with_cancellation(
+        &self,
+        cancel: &CancellationToken,
+    ) -> Result<AuthenticationResponse, super::error::Error> {
+        Ok(self.authenticate_inner(Some(cancel)).await?)
+    }
+
+    /// Removes every stored token, so the next [Self::authenticate]
+    /// goes through the device code flow again
+    pub fn logout(&self) -> Result<(), super::error::Error> {
+        Ok(self.token_store.clear()?)
+    }
+
+    async fn authenticate_inner(
+        &self,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<AuthenticationResponse, super::error::AuthenticationError> {
+        if let Some(resp) = self.token_from_env() {
+            tracing::debug!("using the access token from {}", ACCESS_TOKEN_VAR);
+            return Ok(resp);
+        }
+        let now = SystemTime::now();
+        match self.token_store.load_async().await {
+            Some(token) if !token.is_expired(now) => return Ok(token.to_response(now)),
+            Some(_) => {
+                if let Ok(resp) = self.authenticate_with_refresh_token().await {
+                    return Ok(resp);
+                }
+            }
+            None => {}
+        }
+        self.authenticate_with_device_code(cancel).await
+    }
+
+    /// The granted scopes are unknown, so the requested ones are assumed.
+    /// So is the lifetime, `expires_in` being 0: [TokenProvider] keeps using
+    /// the token until the server rejects it, and never stores its refreshes.
+    pub(crate) fn token_from_env(&self) -> Option<AuthenticationResponse> {
+        let access_token = std::env::var(ACCESS_TOKEN_VAR)
+            .ok()
+            .filter(|token| !token.is_empty())?;
+        Some(AuthenticationResponse {
+            token_type: "Bearer".to_owned(),
+            scope: self.scope.clone(),
+            expires_in: 0,
+            ext_expires_in: 0,
+            access_token,
+            refresh_token: std::env::var(REFRESH_TOKEN_VAR).unwrap_or_default(),
+            id_token: None,
+        })
+    }
+
+    /// First half of the device code flow: gets a code for the user to enter
+    /// at [DeviceCodeHandle::verification_link]. Nothing is presented,
+    /// the caller showing the code before waiting with [Self::poll].
+    pub async fn start_device_code(&self) -> Result<DeviceCodeHandle, super::error::Error> {
+        Ok(self.start_device_code_inner().await?)
+    }
+
+    /// Second half of the device code flow: waits for the user to enter the code
+    /// of `handle`, then stores and returns the token
+    pub async fn poll(
+        &self,
+        handle: &DeviceCodeHandle,
+    ) -> Result<AuthenticationResponse, super::error::Error> {
+        Ok(self.poll_inner(handle, None).await?)
+    }
+
+    /// Same as [Self::poll], stopping with
+    /// [super::error::AuthenticationError::Cancelled] once `cancel` is cancelled
+    pub async fn poll_with_cancellation(
+        &self,
+        handle: &DeviceCodeHandle,
+        cancel: &CancellationToken,
+    ) -> Result<AuthenticationResponse, super::error::Error> {
+        Ok(self.poll_inner(handle, Some(cancel)).await?)
+    }
+
+    async fn authenticate_with_device_code(
+        &self,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<AuthenticationResponse, super::error::AuthenticationError> {
+        let handle = self.start_device_code_inner().await?;
+        self.presenter.present(
+            &handle.user_code,
+            handle.verification_link(),
+            &handle.message,
+        );
+        if self.open_browser {
+            open_in_browser(handle.verification_link());
+        }
+        self.poll_inner(&handle, cancel).await
+    }
+
+    async fn start_device_code_inner(
+        &self,
+    ) -> Result<DeviceCodeHandle, super::error::AuthenticationError> {
+        let req_body = requests::DeviceCodeAuthenticationRequest {
+            client_id: &self.client_id,
+            scope: &self.scope,
+        };
+        tracing::debug!(
+            client_id = req_body.client_id,
+            scope = req_body.scope,
+            "requesting device code"
+        );
+        let resp_raw = self
+            .post_form(&self.device_code_endpoint, &req_body)
+            .await?;
+        if !resp_raw.status().is_success() {
+            let body = resp_raw.text().await?;
+            return Err(device_code_error(body));
+        }
+        let resp = resp_raw.json::<DeviceCodeAuthenticationResponse>().await?;
+        tracing::debug!(verification_uri = %resp.verification_uri, "received device code");
+        Ok(DeviceCodeHandle::new(resp))
+    }
+
+    /// Wait before the next poll, `pending` being the number of consecutive
+    /// `authorization_pending` answers, see [DeviceCodeAuthenticationBuilder::pending_backoff]
+    fn next_interval(&self, poll_interval: Duration, pending: u32) -> Duration {
+        match &self.pending_backoff {
+            Some(backoff) => backoff.interval(poll_interval, pending),
+            None => poll_interval,
+        }
+    }
+
+    async fn poll_inner(
+        &self,
+        handle: &DeviceCodeHandle,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<AuthenticationResponse, super::error::AuthenticationError> {
+        let mut poll_interval = handle.interval;
+        // polling for authentication status as instructed by the server
+        // until the device code expires, even if the server never says so
+        let deadline = handle.deadline;
+        let poll_req = requests::AuthenticationRequest::new(&self.client_id, &handle.device_code);
+        let mut attempt: u32 = 0;
+        // consecutive `authorization_pending` answers
+        let mut pending: u32 = 0;
+        loop {
+            attempt += 1;
+            let interval = self.next_interval(poll_interval, pending);
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                break Err(crate::error::AuthenticationError::DeviceCodeExpired(
+                    crate::error::OAuthError::new(&format!(
+                        "device code was not authorized within {} seconds",
+                        handle.expires_in.as_secs()
+                    )),
+                ));
+            }
+            if let Some(on_poll) = &self.on_poll {
+                let status = PollStatus {
+                    attempt,
+                    interval,
+                    seconds_remaining: (deadline - now).as_secs(),
+                };
+                if let Ok(mut on_poll) = on_poll.lock() {
+                    on_poll(status);
+                }
+            }
+            tracing::trace!(
+                attempt,
+                interval = interval.as_secs(),
+                device_code = "<redacted>",
+                "polling for authorization"
+            );
+            let poll_resp_raw = self.post_form(&self.token_endpoint, &poll_req).await?;
+
+            let status = poll_resp_raw.status();
+
+            // user has authorized the device code
+            if status.is_success() {
+                let res = poll_resp_raw.json().await?;
+                self.token_store.save_async(&res).await?;
+                break Ok(res);
+            }
+            let poll_err = poll_resp_raw
+                .json::<responses::DeviceCodeAuthenticationError>()
+                .await
+                .map_err(|e| {
+                    crate::error::AuthenticationError::UnexpectedResponse(e.to_string())
+                })?;
+            let error = poll_err.error.clone();
+            let details = crate::error::OAuthError::from(poll_err);
+            if !matches!(
+                error,
+                AuthorizationError::AuthorizationPending | AuthorizationError::SlowDown
+            ) {
+                tracing::error!(
+                    error = ?error,
+                    trace_id = details.trace_id,
+                    correlation_id = details.correlation_id,
+                    "authorization failed"
+                );
+            }
+            match error {
+                AuthorizationError::AuthorizationPending => pending += 1,
+                // the spec asks clients to back off by 5 seconds on every `slow_down`
+                AuthorizationError::SlowDown => {
+                    poll_interval += SLOW_DOWN_INCREMENT;
+                    pending = 0;
+                }
+                AuthorizationError::AuthorizationDeclined => {
+                    break Err(crate::error::AuthenticationError::AuthorizationDeclined(
+                        details,
+                    ))
+                }
+                AuthorizationError::ExpiredToken => {
+                    break Err(crate::error::AuthenticationError::DeviceCodeExpired(
+                        details,
+                    ))
+                }
+                AuthorizationError::BadVerificationCode => {
+                    break Err(crate::error::AuthenticationError::BadVerificationCode(
+                        details,
+                    ))
+                }
+                AuthorizationError::InvalidClient => {
+                    break Err(crate::error::AuthenticationError::InvalidClient(details))
+                }
+                AuthorizationError::InvalidScope => {
+                    break Err(crate::error::AuthenticationError::InvalidScope(details))
+                }
+                AuthorizationError::Unknown => {
+                    break Err(crate::error::AuthenticationError::AuthorizationFailed(
+                        details,
+                    ))
+                }
+            }
+            let interval = self.next_interval(poll_interval, pending);
+            let wait = retry::jitter(interval, self.poll_jitter);
+            match cancel {
+                Some(cancel) => tokio::select! {
+                    _ = cancel.cancelled() => {
+                        break Err(crate::error::AuthenticationError::Cancelled)
+                    }
+                    _ = tokio::time::sleep(wait) => {}
+                },
+                None => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    async fn authenticate_with_refresh_token(
+        &self,
+    ) -> Result<AuthenticationResponse, super::error::AuthenticationError> {
+        let stored = self
+            .token_store
+            .load_async()
+            .await
+            .ok_or(super::error::AuthenticationError::NoStoredToken)?;
+        self.refresh_inner(&stored.refresh_token, true).await
+    }
+
+    /// Exchanges `refresh_token` for a new access token and stores the result
+    pub async fn refresh(
+        &self,
+        refresh_token: &str,
+    ) -> Result<AuthenticationResponse, super::error::Error> {
+        Ok(self.refresh_inner(refresh_token, true).await?)
+    }
+
+    /// Same as [Self::refresh] without storing the result,
+    /// for tokens that did not come from the store
+    pub(crate) async fn refresh_unstored(
+        &self,
+        refresh_token: &str,
+    ) -> Result<AuthenticationResponse, super::error::AuthenticationError> {
+        self.refresh_inner(refresh_token, false).await
+    }
+
+    async fn refresh_inner(
+        &self,
+        refresh_token: &str,
+        store: bool,
+    ) -> Result<AuthenticationResponse, super::error::AuthenticationError> {
+        if refresh_token.is_empty() {
+            return Err(super::error::AuthenticationError::NoRefreshToken);
+        }
+        let req_body =
+            requests::RefreshTokenRequest::new(&self.client_id, &self.scope, refresh_token);
+        let resp_raw = self.post_form(&self.token_endpoint, &req_body).await?;
+        if !resp_raw.status().is_success() {
+            return Err(super::error::AuthenticationError::UnexpectedResponse(
+                resp_raw.text().await?,
+            ));
+        }
+        let mut resp = resp_raw.json::<AuthenticationResponse>().await?;
+        // a refresh token is only returned when rotated, the old one staying valid otherwise
+        if resp.refresh_token.is_empty() {
+            resp.refresh_token = refresh_token.to_owned();
+        }
+        // the server rotates the refresh token, so the old one must be replaced
+        if store {
+            self.token_store.save_async(&resp).await?;
+        }
+        Ok(resp)
+    }
+
+    /// Posts a form, retrying transient failures according to the [RetryPolicy]
+    async fn post_form<T: serde::Serialize + ?Sized>(
+        &self,
+        url: &str,
+        form: &T,
+    ) -> Result<reqwest::Response, super::error::AuthenticationError> {
+        let mut attempt = 1;
+        loop {
+            let mut req = self
+                .http_client
+                .post(url)
+                .headers(self.default_headers.clone())
+                .form(form);
+            if let Some(timeout) = self.timeout {
+                req = req.timeout(timeout);
+            }
+            let result = req.send().await;
+            if attempt >= self.retry_policy.max_attempts || !RetryPolicy::is_retryable(&result) {
+                return Ok(result?);
+            }
+            let delay = result
+                .ok()
+                .and_then(|resp| crate::utils::retry_after(resp.headers()))
+                .unwrap_or_else(|| self.retry_policy.backoff(attempt));
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(feature = "browser")]
+fn open_in_browser(uri: &str) {
+    if let Err(e) = open::that_detached(uri) {
+        tracing::warn!("failed to open {} in a browser: {}", uri, e);
+    }
+}
+
+#[cfg(not(feature = "browser"))]
+fn open_in_browser(uri: &str) {
+    tracing::warn!(
+        "unable to open {}, mstodo was built without the `browser` feature",
+        uri
+    );
+}
+
+/// Error for a failed device code request, typed when the server
+/// explains the failure with a code this crate knows
+fn device_code_error(body: String) -> crate::error::AuthenticationError {
+    let Ok(err) = serde_json::from_str::<DeviceCodeAuthenticationError>(&body) else {
+        return crate::error::AuthenticationError::UnexpectedResponse(body);
+    };
+    tracing::error!(
+        error = ?err.error,
+        trace_id = err.trace_id,
+        correlation_id = err.correlation_id,
+        "device code request failed"
+    );
+    match err.error {
+        AuthorizationError::InvalidClient => {
+            crate::error::AuthenticationError::InvalidClient(err.into())
+        }
+        AuthorizationError::InvalidScope => {
+            crate::error::AuthenticationError::InvalidScope(err.into())
+        }
+        _ => crate::error::AuthenticationError::UnexpectedResponse(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_string_contains, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::testing;
+
+    /// Answers the token requests with each of `errors` in turn, then with a token
+    async fn mount_poll_answers(server: &MockServer, errors: &[&str]) {
+        Mock::given(method("POST"))
+            .and(path(testing::DEVICE_CODE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(testing::device_code_json()))
+            .mount(server)
+            .await;
+        for error in errors {
+            Mock::given(method("POST"))
+                .and(path(testing::TOKEN_PATH))
+                .respond_with(
+                    ResponseTemplate::new(400).set_body_json(testing::oauth_error_json(error)),
+                )
+                .up_to_n_times(1)
+                .mount(server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::token_json("access", "refresh")),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn refresh_form_carries_the_refresh_token() {
+        let form = requests::RefreshTokenRequest::new(CLIENT_ID, "offline_access", "old-refresh");
+        let req = reqwest::Client::new()
+            .post("http://localhost/token")
+            .form(&form)
+            .build()
+            .unwrap();
+        let body = std::str::from_utf8(req.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert!(body.contains("grant_type=refresh_token"), "{}", body);
+        assert!(body.contains("refresh_token=old-refresh"), "{}", body);
+        assert!(body.contains("offline_access"), "{}", body);
+    }
+
+    #[test]
+    fn token_response_carries_the_rotated_refresh_token() {
+        let resp: AuthenticationResponse = serde_json::from_value(serde_json::json!({
+            "token_type": "Bearer",
+            "scope": "Tasks.ReadWrite User.Read",
+            "expires_in": 3600,
+            "ext_expires_in": 3600,
+            "access_token": "new-access",
+            "refresh_token": "new-refresh",
+        }))
+        .unwrap();
+        assert_eq!(resp.access_token, "new-access");
+        assert_eq!(resp.refresh_token, "new-refresh");
+        assert_eq!(resp.id_token, None);
+    }
+
+    #[test]
+    fn slow_down_is_understood() {
+        let err: DeviceCodeAuthenticationError = serde_json::from_value(serde_json::json!({
+            "error": "slow_down",
+            "error_description": "AADSTS70000: slow down",
+            "error_codes": [70000],
+            "timestamp": "2024-05-01 00:00:00Z",
+            "trace_id": "trace",
+            "correlation_id": "correlation",
+        }))
+        .unwrap();
+        assert_eq!(err.error, AuthorizationError::SlowDown);
+        assert_eq!(SLOW_DOWN_INCREMENT, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn terminal_errors_are_understood() {
+        for (code, expected) in [
+            (
+                "authorization_declined",
+                AuthorizationError::AuthorizationDeclined,
+            ),
+            ("expired_token", AuthorizationError::ExpiredToken),
+            (
+                "bad_verification_code",
+                AuthorizationError::BadVerificationCode,
+            ),
+        ] {
+            let error: AuthorizationError = serde_json::from_value(code.into()).unwrap();
+            assert_eq!(error, expected);
+        }
+        let err = crate::error::AuthenticationError::AuthorizationDeclined(
+            crate::error::OAuthError::new("AADSTS70000: the user declined"),
+        );
+        assert_eq!(
+            err.to_string(),
+            "Authorization declined: AADSTS70000: the user declined"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polling_stops_once_the_code_expires() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut body = testing::device_code_json();
+        body["expires_in"] = 3.into();
+        Mock::given(method("POST"))
+            .and(path(testing::DEVICE_CODE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(testing::oauth_error_json("authorization_pending")),
+            )
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+
+        let start = tokio::time::Instant::now();
+        let err = auth.authenticate_with_device_code(None).await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::AuthenticationError::DeviceCodeExpired(_)
+        ));
+        assert_eq!(start.elapsed().as_secs(), 3);
+        // the device code request, then a poll every second
+        assert_eq!(server.received_requests().await.unwrap().len(), 1 + 3);
+    }
+
+    #[tokio::test]
+    async fn authenticate_uses_a_valid_stored_token() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+        auth.token_store
+            .save(&testing::auth_response("stored", "refresh"))
+            .unwrap();
+
+        assert_eq!(auth.authenticate().await.unwrap().access_token, "stored");
+    }
+
+    #[tokio::test]
+    async fn authenticate_refreshes_an_expired_token() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .and(body_string_contains("refresh_token=stored-refresh"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::token_json("new", "rotated")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+        let mut expired = testing::auth_response("stored", "stored-refresh");
+        expired.expires_in = 0;
+        auth.token_store.save(&expired).unwrap();
+
+        assert_eq!(auth.authenticate().await.unwrap().access_token, "new");
+        assert_eq!(auth.token_store.load().unwrap().refresh_token, "rotated");
+    }
+
+    #[tokio::test]
+    async fn authenticate_falls_back_to_the_device_code() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_poll_answers(&server, &[]).await;
+        let auth = testing::authenticator(&server, dir.path());
+
+        assert_eq!(auth.authenticate().await.unwrap().access_token, "access");
+        assert_eq!(auth.token_store.load().unwrap().access_token, "access");
+    }
+
+    #[tokio::test]
+    async fn authenticate_falls_back_to_the_device_code_when_refresh_fails() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(testing::oauth_error_json("invalid_grant")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        mount_poll_answers(&server, &[]).await;
+        let auth = testing::authenticator(&server, dir.path());
+        let mut expired = testing::auth_response("stored", "revoked");
+        expired.expires_in = 0;
+        auth.token_store.save(&expired).unwrap();
+
+        assert_eq!(auth.authenticate().await.unwrap().access_token, "access");
+    }
+
+    #[tokio::test]
+    async fn refresh_stores_the_rotated_refresh_token() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .and(body_string_contains("refresh_token=old-refresh"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(testing::token_json("new-access", "new-refresh")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+
+        let resp = auth.refresh("old-refresh").await.unwrap();
+        assert_eq!(resp.access_token, "new-access");
+        let stored = auth.token_store.load().unwrap();
+        assert_eq!(stored.access_token, "new-access");
+        assert_eq!(stored.refresh_token, "new-refresh");
+    }
+
+    #[test]
+    fn refresh_token_is_optional() {
+        let mut body = testing::token_json("access", "refresh");
+        body.as_object_mut().unwrap().remove("refresh_token");
+        let resp: AuthenticationResponse = serde_json::from_value(body).unwrap();
+        assert_eq!(resp.refresh_token, "");
+    }
+
+    #[tokio::test]
+    async fn refresh_keeps_the_refresh_token_when_not_rotated() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut body = testing::token_json("new-access", "");
+        body.as_object_mut().unwrap().remove("refresh_token");
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+        auth.token_store
+            .save(&testing::auth_response("old-access", "old-refresh"))
+            .unwrap();
+
+        let resp = auth.authenticate_with_refresh_token().await.unwrap();
+        assert_eq!(resp.refresh_token, "old-refresh");
+        assert_eq!(
+            auth.token_store.load().unwrap().refresh_token,
+            "old-refresh"
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_without_refresh_token_sends_nothing() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+        auth.token_store
+            .save(&testing::auth_response("access", ""))
+            .unwrap();
+
+        let err = auth.authenticate_with_refresh_token().await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::AuthenticationError::NoRefreshToken
+        ));
+    }
+
+    #[test]
+    fn builder_overrides_the_app_registration() {
+        let auth = DeviceCodeAuthentication::builder()
+            .client_id("client")
+            .scopes(&["offline_access", "Tasks.Read"])
+            .build()
+            .unwrap();
+        assert_eq!(auth.client_id, "client");
+        assert_eq!(auth.scope, "offline_access Tasks.Read");
+        assert_eq!(
+            auth.token_endpoint,
+            format!(
+                "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+                TENANT
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_go_through_the_supplied_client() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .and(wiremock::matchers::header("x-proxy-auth", "secret"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::token_json("new", "refresh")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-proxy-auth", "secret".parse().unwrap());
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let auth = testing::auth_builder(&server, dir.path())
+            .http_client(client)
+            .build()
+            .unwrap();
+        auth.token_store
+            .save(&testing::auth_response("old", "refresh"))
+            .unwrap();
+
+        let resp = auth.authenticate_with_refresh_token().await.unwrap();
+        assert_eq!(resp.access_token, "new");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn post_form_retries_server_errors() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::DEVICE_CODE_PATH))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::DEVICE_CODE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(testing::device_code_json()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let auth = testing::auth_builder(&server, dir.path())
+            .retry_policy(RetryPolicy::new(3, Duration::from_millis(500)))
+            .build()
+            .unwrap();
+
+        let start = tokio::time::Instant::now();
+        let resp = auth
+            .post_form(&auth.device_code_endpoint, &[("client_id", "client")])
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        // 500ms then 1s
+        assert_eq!(start.elapsed(), Duration::from_millis(1500));
+    }
+
+    #[tokio::test]
+    async fn post_form_gives_up_after_the_last_attempt() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let auth = testing::auth_builder(&server, dir.path())
+            .retry_policy(RetryPolicy::new(2, Duration::ZERO))
+            .build()
+            .unwrap();
+
+        let err = auth.authenticate_with_device_code(None).await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::AuthenticationError::UnexpectedResponse(_)
+        ));
+    }
+
+    /// Buffer the events of a test are formatted into
+    #[derive(Clone, Default)]
+    struct Logs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl tracing_subscriber::fmt::MakeWriter<'_> for Logs {
+        type Writer = Logs;
+
+        fn make_writer(&self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn device_code_flow_is_traced_without_the_device_code() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_poll_answers(&server, &[]).await;
+        let auth = testing::authenticator(&server, dir.path());
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(logs.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        auth.authenticate_with_device_code(None).await.unwrap();
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("requesting device code"), "{}", logs);
+        assert!(logs.contains("polling for authorization"), "{}", logs);
+        assert!(!logs.contains("device-code"), "{}", logs);
+    }
+
+    #[tokio::test]
+    async fn presenter_gets_the_code_to_show() {
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<std::sync::Mutex<Vec<(String, String, String)>>>);
+
+        impl DeviceCodePresenter for Recorder {
+            fn present(&self, user_code: &str, verification_uri: &str, message: &str) {
+                self.0.lock().unwrap().push((
+                    user_code.to_owned(),
+                    verification_uri.to_owned(),
+                    message.to_owned(),
+                ));
+            }
+        }
+
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_poll_answers(&server, &[]).await;
+        let recorder = Recorder::default();
+        let auth = testing::auth_builder(&server, dir.path())
+            .presenter(recorder.clone())
+            .build()
+            .unwrap();
+
+        auth.authenticate_with_device_code(None).await.unwrap();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![(
+                "ABCD-EFGH".to_owned(),
+                "https://microsoft.com/devicelogin".to_owned(),
+                "Enter ABCD-EFGH at https://microsoft.com/devicelogin".to_owned(),
+            )]
+        );
+    }
+
+    #[test]
+    fn device_code_response_without_complete_uri() {
+        let resp: DeviceCodeAuthenticationResponse =
+            serde_json::from_value(testing::device_code_json()).unwrap();
+        let handle = DeviceCodeHandle::new(resp);
+        assert_eq!(handle.verification_uri_complete, None);
+        assert_eq!(
+            handle.verification_link(),
+            "https://microsoft.com/devicelogin"
+        );
+    }
+
+    #[test]
+    fn device_code_response_with_complete_uri() {
+        let mut body = testing::device_code_json();
+        body["verification_uri_complete"] =
+            "https://microsoft.com/devicelogin?otc=ABCD-EFGH".into();
+        let resp: DeviceCodeAuthenticationResponse = serde_json::from_value(body).unwrap();
+        let handle = DeviceCodeHandle::new(resp);
+        assert_eq!(
+            handle.verification_link(),
+            "https://microsoft.com/devicelogin?otc=ABCD-EFGH"
+        );
+    }
+
+    #[cfg(not(feature = "browser"))]
+    #[tokio::test]
+    async fn open_browser_without_the_feature_only_warns() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_poll_answers(&server, &[]).await;
+        let auth = testing::auth_builder(&server, dir.path())
+            .open_browser(true)
+            .build()
+            .unwrap();
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(logs.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        auth.authenticate_with_device_code(None).await.unwrap();
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            logs.contains("built without the `browser` feature"),
+            "{}",
+            logs
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelling_stops_polling_right_away() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut body = testing::device_code_json();
+        body["interval"] = 5.into();
+        Mock::given(method("POST"))
+            .and(path(testing::DEVICE_CODE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(testing::oauth_error_json("authorization_pending")),
+            )
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(7)).await;
+            canceller.cancel();
+        });
+
+        let start = tokio::time::Instant::now();
+        let err = auth
+            .authenticate_with_cancellation(&cancel)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::Error::Auth(crate::error::AuthenticationError::Cancelled)
+        ));
+        // not waiting for the poll due at 10 seconds
+        assert_eq!(start.elapsed(), Duration::from_secs(7));
+    }
+
+    /// Authenticator recording the status passed to every `on_poll`
+    fn recording_authenticator(
+        server: &MockServer,
+        dir: &std::path::Path,
+    ) -> (DeviceCodeAuthentication, Arc<Mutex<Vec<PollStatus>>>) {
+        let polls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = polls.clone();
+        let auth = testing::auth_builder(server, dir)
+            .on_poll(move |status| recorded.lock().unwrap().push(status))
+            .build()
+            .unwrap();
+        (auth, polls)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_down_lengthens_the_interval_by_5_seconds() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_poll_answers(&server, &["slow_down", "slow_down"]).await;
+        let (auth, polls) = recording_authenticator(&server, dir.path());
+
+        auth.authenticate_with_device_code(None).await.unwrap();
+        let intervals: Vec<u64> = polls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|status| status.interval.as_secs())
+            .collect();
+        assert_eq!(intervals, vec![1, 6, 11]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn on_poll_is_called_before_every_poll() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_poll_answers(&server, &["authorization_pending", "authorization_pending"]).await;
+        let (auth, polls) = recording_authenticator(&server, dir.path());
+
+        auth.authenticate_with_device_code(None).await.unwrap();
+        let polls = polls.lock().unwrap();
+        assert_eq!(polls.len(), 3);
+        let attempts: Vec<u32> = polls.iter().map(|status| status.attempt).collect();
+        assert_eq!(attempts, vec![1, 2, 3]);
+        let remaining: Vec<u64> = polls
+            .iter()
+            .map(|status| status.seconds_remaining)
+            .collect();
+        assert_eq!(remaining, vec![900, 899, 898]);
+    }
+
+    #[test]
+    fn has_scope_present_and_absent() {
+        let mut body = testing::token_json("access", "refresh");
+        body["scope"] = "openid https://graph.microsoft.com/Tasks.ReadWrite User.Read".into();
+        let resp: AuthenticationResponse = serde_json::from_value(body).unwrap();
+        assert!(resp.has_scope("Tasks.ReadWrite"));
+        assert!(resp.has_scope("tasks.readwrite"));
+        assert!(resp.has_scope("User.Read"));
+        assert!(!resp.has_scope("Tasks.Read"));
+        assert!(!resp.has_scope("Mail.Read"));
+
+        let mut body = testing::token_json("access", "refresh");
+        body["scope"] = "".into();
+        let resp: AuthenticationResponse = serde_json::from_value(body).unwrap();
+        assert!(!resp.has_scope("Tasks.ReadWrite"));
+    }
+
+    #[test]
+    fn unknown_error_code_is_parsed() {
+        let err: DeviceCodeAuthenticationError =
+            serde_json::from_value(testing::oauth_error_json("quantum_entanglement_required"))
+                .unwrap();
+        assert_eq!(err.error, AuthorizationError::Unknown);
+        assert_eq!(
+            err.error_description,
+            "AADSTS00000: quantum_entanglement_required"
+        );
+    }
+
+    #[test]
+    fn responses_round_trip_through_json() {
+        let token = testing::token_json("access", "refresh");
+        let auth: AuthenticationResponse = serde_json::from_value(token.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&auth).unwrap(), token);
+
+        let mut device_code = testing::device_code_json();
+        let resp: DeviceCodeAuthenticationResponse =
+            serde_json::from_value(device_code.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&resp).unwrap(), device_code);
+        device_code["verification_uri_complete"] =
+            "https://microsoft.com/devicelogin?otc=ABCD-EFGH".into();
+        let resp: DeviceCodeAuthenticationResponse =
+            serde_json::from_value(device_code.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&resp).unwrap(), device_code);
+
+        let error = testing::oauth_error_json("authorization_declined");
+        let err: DeviceCodeAuthenticationError = serde_json::from_value(error.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&err).unwrap(), error);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn misspelled_error_alias_still_deserializes() {
+        let err: responses::DeviceCodeAhenticationError =
+            serde_json::from_value(testing::oauth_error_json("expired_token")).unwrap();
+        assert_eq!(err.error, AuthorizationError::ExpiredToken);
+    }
+
+    #[tokio::test]
+    async fn unknown_error_code_fails_the_authorization() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_poll_answers(&server, &["quantum_entanglement_required"]).await;
+        let auth = testing::authenticator(&server, dir.path());
+
+        let err = auth.authenticate_with_device_code(None).await.unwrap_err();
+        match err {
+            crate::error::AuthenticationError::AuthorizationFailed(details) => {
+                assert_eq!(
+                    details.description,
+                    "AADSTS00000: quantum_entanglement_required"
+                );
+                assert_eq!(details.trace_id.as_deref(), Some("trace"));
+                assert_eq!(details.correlation_id.as_deref(), Some("correlation"));
+            }
+            err => panic!("unexpected {:?}", err),
+        }
+    }
+
+    #[test]
+    fn poll_form_carries_the_device_code() {
+        let form = requests::AuthenticationRequest::new("client", "the-code");
+        let req = reqwest::Client::new()
+            .post("http://localhost/token")
+            .form(&form)
+            .build()
+            .unwrap();
+        let body = std::str::from_utf8(req.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert!(body.contains("device_code=the-code"), "{}", body);
+        assert!(!body.contains("&code="), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn start_device_code_then_poll() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::DEVICE_CODE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(testing::device_code_json()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .and(body_string_contains("device_code=device-code"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::token_json("access", "refresh")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+
+        let handle = auth.start_device_code().await.unwrap();
+        assert_eq!(handle.user_code, "ABCD-EFGH");
+        assert_eq!(handle.interval, Duration::from_secs(1));
+        assert_eq!(
+            handle.verification_link(),
+            "https://microsoft.com/devicelogin"
+        );
+
+        let resp = auth.poll(&handle).await.unwrap();
+        assert_eq!(resp.access_token, "access");
+        assert_eq!(auth.token_store.load().unwrap().refresh_token, "refresh");
+    }
+
+    #[tokio::test]
+    async fn poll_reports_a_declined_authorization() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::DEVICE_CODE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(testing::device_code_json()))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(testing::oauth_error_json("authorization_declined")),
+            )
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+
+        let handle = auth.start_device_code().await.unwrap();
+        let err = auth.poll(&handle).await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::Error::Auth(crate::error::AuthenticationError::AuthorizationDeclined(_))
+        ));
+        assert!(auth.token_store.load().is_none());
+    }
+
+    #[test]
+    fn blank_device_code_message_falls_back() {
+        for message in ["", "  \n"] {
+            let mut body = testing::device_code_json();
+            body["message"] = message.into();
+            let resp: DeviceCodeAuthenticationResponse = serde_json::from_value(body).unwrap();
+            let handle = DeviceCodeHandle::new(resp);
+            assert_eq!(
+                handle.message,
+                fallback_message("ABCD-EFGH", "https://microsoft.com/devicelogin")
+            );
+        }
+        let resp: DeviceCodeAuthenticationResponse =
+            serde_json::from_value(testing::device_code_json()).unwrap();
+        assert_eq!(
+            DeviceCodeHandle::new(resp).message,
+            "Enter ABCD-EFGH at https://microsoft.com/devicelogin"
+        );
+    }
+
+    #[test]
+    fn decode_unsigned_id_token() {
+        use base64::Engine;
+
+        let encode = |value: serde_json::Value| {
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(value.to_string())
+        };
+        let id_token = format!(
+            "{}.{}.",
+            encode(serde_json::json!({ "alg": "none", "typ": "JWT" })),
+            encode(serde_json::json!({
+                "aud": "client",
+                "preferred_username": "ada@example.com",
+                "name": "Ada Lovelace",
+                "oid": "00000000-0000-0000-0000-0000000000aa",
+                "tid": testing::TENANT,
+            })),
+        );
+        let mut body = testing::token_json("access", "refresh");
+        body["id_token"] = id_token.into();
+        let resp: AuthenticationResponse = serde_json::from_value(body).unwrap();
+
+        assert_eq!(
+            resp.decode_id_token().unwrap(),
+            Some(IdTokenClaims {
+                preferred_username: Some("ada@example.com".to_owned()),
+                name: Some("Ada Lovelace".to_owned()),
+                oid: Some("00000000-0000-0000-0000-0000000000aa".to_owned()),
+                tid: Some(testing::TENANT.to_owned()),
+            })
+        );
+        assert_eq!(
+            testing::auth_response("access", "refresh")
+                .decode_id_token()
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn decode_malformed_id_token() {
+        let mut body = testing::token_json("access", "refresh");
+        body["id_token"] = "not-a-jwt".into();
+        let resp: AuthenticationResponse = serde_json::from_value(body).unwrap();
+        assert!(matches!(
+            resp.decode_id_token(),
+            Err(crate::error::AuthenticationError::UnexpectedResponse(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn device_code_request_with_an_invalid_client() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::DEVICE_CODE_PATH))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(testing::oauth_error_json("invalid_client")),
+            )
+            .mount(&server)
+            .await;
+        let auth = testing::authenticator(&server, dir.path());
+
+        let err = auth.start_device_code().await.unwrap_err();
+        match err {
+            crate::error::Error::Auth(crate::error::AuthenticationError::InvalidClient(
+                details,
+            )) => {
+                assert_eq!(details.description, "AADSTS00000: invalid_client");
+                assert_eq!(details.correlation_id.as_deref(), Some("correlation"));
+            }
+            err => panic!("unexpected {:?}", err),
+        }
+    }
+
+    #[test]
+    fn unparseable_device_code_error_keeps_the_body() {
+        let body = "<html>Service Unavailable</html>".to_owned();
+        assert!(matches!(
+            device_code_error(body),
+            crate::error::AuthenticationError::UnexpectedResponse(body)
+                if body == "<html>Service Unavailable</html>"
+        ));
+        let body = testing::oauth_error_json("invalid_scope").to_string();
+        assert!(matches!(
+            device_code_error(body),
+            crate::error::AuthenticationError::InvalidScope(_)
+        ));
+    }
+
+    #[test]
+    fn debug_output_has_no_secrets() {
+        let mut body = testing::token_json("secret-access", "secret-refresh");
+        body["id_token"] = "secret-id".into();
+        let resp: AuthenticationResponse = serde_json::from_value(body).unwrap();
+        let stored = store::StoredToken::from_response(&resp, SystemTime::now());
+        let handle =
+            DeviceCodeHandle::new(serde_json::from_value(testing::device_code_json()).unwrap());
+        for debug in [
+            format!("{:?}", resp),
+            format!("{:#?}", resp),
+            format!("{:?}", stored),
+            format!("{:?}", handle),
+        ] {
+            assert!(!debug.contains("secret"), "{}", debug);
+            assert!(!debug.contains("device-code"), "{}", debug);
+        }
+        assert!(format!("{:?}", resp).contains("<redacted, 13 chars>"));
+        assert!(format!("{:?}", handle).contains("ABCD-EFGH"));
+    }
+
+    #[tokio::test]
+    async fn pending_backoff_only_when_enabled() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let base = Duration::from_secs(1);
+        let fixed = testing::authenticator(&server, dir.path());
+        assert_eq!(fixed.next_interval(base, 10), base);
+
+        let adaptive = testing::auth_builder(&server, dir.path())
+            .pending_backoff(PendingBackoff::default())
+            .build()
+            .unwrap();
+        let intervals: Vec<_> = (3..7)
+            .map(|pending| adaptive.next_interval(base, pending))
+            .collect();
+        assert_eq!(
+            intervals,
+            vec![
+                base,
+                Duration::from_millis(1500),
+                Duration::from_millis(2250),
+                Duration::from_secs_f64(3.375),
+            ]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+/// Shows the user how to authorize the device code,
+/// so frontends can render the prompt however they want
+pub trait DeviceCodePresenter {
+    fn present(&self, user_code: &str, verification_uri: &str, message: &str);
+}
+
+/// Prints the server supplied message to stdout, followed by the link
+/// with the code pre-filled when the message does not mention it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutPresenter;
+
+impl DeviceCodePresenter for StdoutPresenter {
+    fn present(&self, _user_code: &str, verification_uri: &str, message: &str) {
+        println!("{}", prompt(verification_uri, message));
+    }
+}
+
+fn prompt(verification_uri: &str, message: &str) -> String {
+    if message.contains(verification_uri) {
+        message.to_owned()
+    } else {
+        format!("{}\nOr open {}", message, verification_uri)
+    }
+}
+
+/// Prompt used when the server sends a blank message, as some tenants do
+pub fn fallback_message(user_code: &str, verification_uri: &str) -> String {
+    format!(
+        "To sign in, use a web browser to open the page {} and enter the code {} to authenticate.",
+        verification_uri, user_code
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &str = "To sign in, use a web browser to open the page \
+                           https://microsoft.com/devicelogin and enter the code ABCD-EFGH.";
+
+    #[test]
+    fn prompt_is_the_message_for_the_plain_uri() {
+        assert_eq!(
+            prompt("https://microsoft.com/devicelogin", MESSAGE),
+            MESSAGE
+        );
+    }
+
+    #[test]
+    fn prompt_adds_the_complete_link() {
+        let link = "https://microsoft.com/devicelogin?otc=ABCD-EFGH";
+        assert_eq!(
+            prompt(link, MESSAGE),
+            format!("{}\nOr open {}", MESSAGE, link)
+        );
+    }
+
+    #[test]
+    fn fallback_message_mentions_the_code_and_uri() {
+        let message = fallback_message("ABCD-EFGH", "https://microsoft.com/devicelogin");
+        assert!(message.contains("ABCD-EFGH"));
+        assert!(message.contains("https://microsoft.com/devicelogin"));
+    }
+}
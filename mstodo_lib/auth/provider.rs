@@ -0,0 +1,204 @@
+//! In-memory copy of the current token, so the token store is only
+//! touched when the token is refreshed.
+use std::{
+    sync::RwLock,
+    time::{Duration, SystemTime},
+};
+
+use super::{responses::AuthenticationResponse, store::EXPIRY_SKEW, DeviceCodeAuthentication};
+use crate::error::{AuthenticationError, Error};
+
+/// Hands out access tokens, refreshing them with its authenticator once expired
+pub struct TokenProvider {
+    token: RwLock<CachedToken>,
+    authenticator: DeviceCodeAuthentication,
+    /// Whether refreshed tokens are saved in the token store,
+    /// not the case for tokens from the environment
+    store_refreshed: bool,
+    /// Held while refreshing so concurrent requests refresh only once
+    refresh_lock: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    auth: AuthenticationResponse,
+    /// [None] when unknown, the token then being used until the server rejects it
+    expires_at: Option<SystemTime>,
+}
+
+impl CachedToken {
+    fn new(auth: AuthenticationResponse) -> Self {
+        let expires_at = Some(auth.expires_at(SystemTime::now()));
+        Self { auth, expires_at }
+    }
+}
+
+impl TokenProvider {
+    /// `auth` is assumed to have just been received
+    pub fn new(auth: AuthenticationResponse, authenticator: DeviceCodeAuthentication) -> Self {
+        Self {
+            token: RwLock::new(CachedToken::new(auth)),
+            authenticator,
+            store_refreshed: true,
+            refresh_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Gets a first token with [DeviceCodeAuthentication::authenticate].
+    /// A token from `MSTODO_ACCESS_TOKEN` is only refreshed once rejected,
+    /// its lifetime being unknown, and its refreshes are not stored.
+    pub async fn login(authenticator: DeviceCodeAuthentication) -> Result<Self, Error> {
+        if let Some(auth) = authenticator.token_from_env() {
+            return Ok(Self {
+                token: RwLock::new(CachedToken {
+                    auth,
+                    expires_at: None,
+                }),
+                authenticator,
+                store_refreshed: false,
+                refresh_lock: tokio::sync::Mutex::new(()),
+            });
+        }
+        let auth = authenticator.authenticate().await?;
+        Ok(Self::new(auth, authenticator))
+    }
+
+    /// The current token, which may be expired
+    pub fn current(&self) -> AuthenticationResponse {
+        self.cached().auth
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.cached().auth.has_scope(scope)
+    }
+
+    /// Time left before the current token expires, [Duration::MAX] when unknown
+    pub fn expires_in(&self) -> Duration {
+        match self.cached().expires_at {
+            Some(expires_at) => expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or_default(),
+            None => Duration::MAX,
+        }
+    }
+
+    /// A valid access token, refreshed first if it is expired or about to be
+    pub async fn access_token(&self) -> Result<String, AuthenticationError> {
+        let cached = self.cached();
+        let expired = cached
+            .expires_at
+            .is_some_and(|expires_at| SystemTime::now() + EXPIRY_SKEW >= expires_at);
+        if !expired {
+            return Ok(cached.auth.access_token);
+        }
+        self.refresh_if_current(&cached.auth.access_token).await?;
+        Ok(self.cached().auth.access_token)
+    }
+
+    /// Replaces the current token with a refreshed one,
+    /// e.g. after the server rejected it
+    pub async fn refresh(&self) -> Result<(), AuthenticationError> {
+        let access_token = self.cached().auth.access_token;
+        self.refresh_if_current(&access_token).await
+    }
+
+    /// Refreshes unless another caller already replaced `access_token` meanwhile
+    async fn refresh_if_current(&self, access_token: &str) -> Result<(), AuthenticationError> {
+        let _guard = self.refresh_lock.lock().await;
+        let cached = self.cached();
+        if cached.auth.access_token != access_token {
+            return Ok(());
+        }
+        let refreshed = if self.store_refreshed {
+            self.authenticator
+                .refresh(&cached.auth.refresh_token)
+                .await
+                .map_err(|_| AuthenticationError::AuthenticationFailed)
+        } else {
+            self.authenticator
+                .refresh_unstored(&cached.auth.refresh_token)
+                .await
+                .map_err(|_| AuthenticationError::AuthenticationFailed)
+        };
+        let auth = refreshed?;
+        if let Ok(mut token) = self.token.write() {
+            *token = CachedToken::new(auth);
+        }
+        Ok(())
+    }
+
+    fn cached(&self) -> CachedToken {
+        match self.token.read() {
+            Ok(token) => token.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_string_contains, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::testing;
+
+    /// Sets the token variables for the duration of `test`
+    async fn with_env_token<F: std::future::Future<Output = ()>>(
+        refresh_token: &str,
+        test: impl FnOnce() -> F,
+    ) {
+        let _guard = testing::ENV_LOCK.lock().await;
+        std::env::set_var(crate::auth::ACCESS_TOKEN_VAR, "env-access");
+        std::env::set_var(crate::auth::REFRESH_TOKEN_VAR, refresh_token);
+        test().await;
+        std::env::remove_var(crate::auth::ACCESS_TOKEN_VAR);
+        std::env::remove_var(crate::auth::REFRESH_TOKEN_VAR);
+    }
+
+    #[tokio::test]
+    async fn env_token_is_used_without_auth_requests() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        with_env_token("", || async {
+            let tokens = TokenProvider::login(testing::authenticator(&server, dir.path()))
+                .await
+                .unwrap();
+            assert_eq!(tokens.access_token().await.unwrap(), "env-access");
+            assert_eq!(tokens.expires_in(), Duration::MAX);
+        })
+        .await;
+        assert!(testing::token_store(dir.path()).load().is_none());
+    }
+
+    #[tokio::test]
+    async fn env_token_refresh_is_not_stored() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .and(body_string_contains("refresh_token=env-refresh"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::token_json("new", "rotated")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        with_env_token("env-refresh", || async {
+            let tokens = TokenProvider::login(testing::authenticator(&server, dir.path()))
+                .await
+                .unwrap();
+            tokens.refresh().await.unwrap();
+            assert_eq!(tokens.access_token().await.unwrap(), "new");
+        })
+        .await;
+        assert!(testing::token_store(dir.path()).load().is_none());
+    }
+}
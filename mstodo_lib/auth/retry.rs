@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+/// How transient failures (5xx responses, connection errors and timeouts)
+/// are retried. The n-th retry waits `base_backoff * 2^(n-1)` unless the
+/// server asks for a specific delay with `Retry-After`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub base_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_backoff,
+        }
+    }
+
+    /// Never retries
+    pub fn none() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
+
+    /// Delay before the given retry, starting from 1
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.base_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+    }
+
+    pub(crate) fn is_retryable(result: &Result<reqwest::Response, reqwest::Error>) -> bool {
+        match result {
+            Ok(resp) => {
+                resp.status().is_server_error()
+                    || resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(e) => e.is_connect() || e.is_timeout(),
+        }
+    }
+}
+
+/// Adaptive polling of the device code flow: once the server answered
+/// `authorization_pending` more than `after` times in a row, each further poll waits
+/// `factor` times longer than the previous one, up to `max_interval`.
+/// Any other answer starts over from the interval asked by the server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingBackoff {
+    pub after: u32,
+    pub factor: f64,
+    pub max_interval: Duration,
+}
+
+impl Default for PendingBackoff {
+    fn default() -> Self {
+        Self {
+            after: 3,
+            factor: 1.5,
+            max_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+impl PendingBackoff {
+    /// Interval after `pending` consecutive `authorization_pending` answers,
+    /// never shorter than `base`, the interval asked by the server
+    pub fn interval(&self, base: Duration, pending: u32) -> Duration {
+        let Some(excess) = pending.checked_sub(self.after).filter(|excess| *excess > 0) else {
+            return base;
+        };
+        // a cap below the server interval leaves it as is
+        let cap = self.max_interval.max(base);
+        let exponent = i32::try_from(excess).unwrap_or(i32::MAX);
+        let grown = base.as_secs_f64() * self.factor.max(1.0).powi(exponent);
+        // too long (or NaN) to be a duration means the cap is reached
+        Duration::try_from_secs_f64(grown).map_or(cap, |grown| grown.clamp(base, cap))
+    }
+}
+
+/// `interval` lengthened by a random amount of up to `fraction` of it,
+/// so that clients started together do not keep polling in lockstep.
+/// It is never shortened, polling faster than asked being answered with `slow_down`.
+pub(crate) fn jitter(interval: Duration, fraction: f64) -> Duration {
+    let fraction = fraction.clamp(0.0, 1.0);
+    if fraction == 0.0 {
+        return interval;
+    }
+    interval.mul_f64(1.0 + fraction * fastrand::f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles() {
+        let policy = RetryPolicy::new(4, Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn jitter_only_lengthens() {
+        let interval = Duration::from_secs(5);
+        for _ in 0..100 {
+            let jittered = jitter(interval, 0.1);
+            assert!(jittered >= interval, "{:?}", jittered);
+            assert!(jittered <= interval.mul_f64(1.1), "{:?}", jittered);
+        }
+    }
+
+    #[test]
+    fn jitter_disabled() {
+        let interval = Duration::from_secs(5);
+        assert_eq!(jitter(interval, 0.0), interval);
+    }
+
+    #[test]
+    fn pending_backoff_grows_up_to_the_cap() {
+        let backoff = PendingBackoff {
+            after: 2,
+            factor: 2.0,
+            max_interval: Duration::from_secs(10),
+        };
+        let base = Duration::from_secs(1);
+        let intervals: Vec<_> = (0..8)
+            .map(|pending| backoff.interval(base, pending).as_secs())
+            .collect();
+        assert_eq!(intervals, vec![1, 1, 1, 2, 4, 8, 10, 10]);
+        // a cap below the server interval never polls faster than asked
+        let capped = PendingBackoff {
+            max_interval: Duration::from_millis(500),
+            ..backoff
+        };
+        assert_eq!(capped.interval(base, 5), base);
+    }
+
+    #[test]
+    fn pending_backoff_never_overflows() {
+        let backoff = PendingBackoff {
+            after: 0,
+            factor: f64::INFINITY,
+            max_interval: Duration::MAX,
+        };
+        let base = Duration::from_secs(5);
+        assert_eq!(backoff.interval(base, 1), Duration::MAX);
+        assert_eq!(backoff.interval(base, u32::MAX), Duration::MAX);
+        let huge = PendingBackoff {
+            factor: 1e300,
+            max_interval: Duration::from_secs(30),
+            ..backoff
+        };
+        assert_eq!(huge.interval(base, 10), Duration::from_secs(30));
+    }
+}
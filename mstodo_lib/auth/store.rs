@@ -0,0 +1,438 @@
+//! Persistence of tokens across process runs.
+//! Tokens are serialized as JSON and saved in the credential store
+//! provided by the OS under the `mstodo` service. When no credential store
+//! is usable (e.g. headless Linux), they are written to `token.json`
+//! under [crate::utils::config_dir] instead, readable only by the owner.
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{responses::AuthenticationResponse, Redacted};
+use crate::error::StoreError;
+
+const KEYRING_SERVICE: &str = "mstodo";
+const DEFAULT_ACCOUNT: &str = "default";
+const TOKEN_FILE: &str = "token.json";
+/// Names of the accounts with a stored token, one per line,
+/// as the keyring cannot be enumerated
+const PROFILES_FILE: &str = "profiles";
+/// Tokens are treated as expired this long before their actual expiry
+/// so they get refreshed before requests start failing
+pub const EXPIRY_SKEW: Duration = Duration::from_secs(60);
+
+/// Token saved in the credential store.
+/// The expiry is kept as an absolute unix timestamp (in seconds)
+/// so it stays meaningful after being reloaded.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct StoredToken {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: String,
+    pub expires_at: u64,
+    #[serde(default)]
+    pub scope: String,
+    #[serde(default)]
+    pub id_token: Option<String>,
+}
+
+/// Tokens are redacted, see [AuthenticationResponse]'s `Debug`
+impl std::fmt::Debug for StoredToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoredToken")
+            .field("access_token", &Redacted(&self.access_token))
+            .field("refresh_token", &Redacted(&self.refresh_token))
+            .field("expires_at", &self.expires_at)
+            .field("scope", &self.scope)
+            .field("id_token", &self.id_token.as_deref().map(Redacted))
+            .finish()
+    }
+}
+
+impl StoredToken {
+    pub fn from_response(resp: &AuthenticationResponse, received_at: SystemTime) -> Self {
+        let expires_at = resp
+            .expires_at(received_at)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self {
+            access_token: resp.access_token.clone(),
+            refresh_token: resp.refresh_token.clone(),
+            expires_at,
+            scope: resp.scope.clone(),
+            id_token: resp.id_token.clone(),
+        }
+    }
+
+    /// Rebuilds the response the token was saved from,
+    /// with `expires_in` counting down from `now`
+    pub fn to_response(&self, now: SystemTime) -> AuthenticationResponse {
+        let expires_in = self
+            .expires_at()
+            .duration_since(now)
+            .unwrap_or_default()
+            .as_secs();
+        AuthenticationResponse {
+            token_type: "Bearer".to_owned(),
+            scope: self.scope.clone(),
+            expires_in,
+            ext_expires_in: expires_in,
+            access_token: self.access_token.clone(),
+            refresh_token: self.refresh_token.clone(),
+            id_token: self.id_token.clone(),
+        }
+    }
+
+    pub fn expires_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.expires_at)
+    }
+
+    /// Whether the access token is expired, or about to within [EXPIRY_SKEW]
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.is_expired_with_skew(now, EXPIRY_SKEW)
+    }
+
+    pub fn is_expired_with_skew(&self, now: SystemTime, skew: Duration) -> bool {
+        now + skew >= self.expires_at()
+    }
+}
+
+/// Saves and loads [StoredToken]s for a single account, or profile.
+/// Tokens of other accounts are kept apart in the keyring and in
+/// `token-{account}.json`, the default account using `token.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenStore {
+    account: String,
+    config_dir: Option<PathBuf>,
+    use_keyring: bool,
+}
+
+impl Default for TokenStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_ACCOUNT)
+    }
+}
+
+impl TokenStore {
+    pub fn new(account: &str) -> Self {
+        Self {
+            account: account.to_owned(),
+            config_dir: crate::utils::config_dir(),
+            use_keyring: true,
+        }
+    }
+
+    /// Overrides the directory used by the file fallback
+    pub fn with_config_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config_dir = Some(dir.into());
+        self
+    }
+
+    /// Only uses the file fallback, e.g. where the credential store prompts the user
+    pub fn without_keyring(mut self) -> Self {
+        self.use_keyring = false;
+        self
+    }
+
+    pub fn account(&self) -> &str {
+        &self.account
+    }
+
+    /// Accounts with a stored token, in the order they were first saved
+    pub fn profiles(&self) -> Vec<String> {
+        self.config_dir
+            .as_ref()
+            .and_then(|dir| fs::read_to_string(dir.join(PROFILES_FILE)).ok())
+            .map(|content| content.lines().map(str::to_owned).collect())
+            .unwrap_or_default()
+    }
+
+    /// Adds or removes this account from the profiles file.
+    /// Failing to do so only affects [Self::profiles], so it is not an error.
+    fn register(&self, registered: bool) {
+        let Some(dir) = &self.config_dir else {
+            return;
+        };
+        let mut profiles = self.profiles();
+        let known = profiles.contains(&self.account);
+        if known == registered {
+            return;
+        }
+        if registered {
+            profiles.push(self.account.clone());
+        } else {
+            profiles.retain(|profile| *profile != self.account);
+        }
+        let content: String = profiles.iter().map(|p| format!("{}\n", p)).collect();
+        if let Err(e) =
+            fs::create_dir_all(dir).and_then(|_| fs::write(dir.join(PROFILES_FILE), content))
+        {
+            tracing::warn!("failed to update the profiles file: {}", e);
+        }
+    }
+
+    fn entry(&self) -> Result<keyring::Entry, StoreError> {
+        if !self.use_keyring {
+            return Err(StoreError::KeyringError(keyring::Error::NoEntry));
+        }
+        Ok(keyring::Entry::new(KEYRING_SERVICE, &self.account)?)
+    }
+
+    fn token_file(&self) -> Result<PathBuf, StoreError> {
+        self.config_dir
+            .as_ref()
+            .map(|dir| dir.join(self.token_file_name()))
+            .ok_or(StoreError::NoConfigDir)
+    }
+
+    fn token_file_name(&self) -> String {
+        if self.account == DEFAULT_ACCOUNT {
+            TOKEN_FILE.to_owned()
+        } else {
+            format!("token-{}.json", self.account)
+        }
+    }
+
+    pub fn save(&self, resp: &AuthenticationResponse) -> Result<(), StoreError> {
+        let token = StoredToken::from_response(resp, SystemTime::now());
+        let raw = serde_json::to_string(&token)?;
+        match self.save_to_keyring(&raw) {
+            Ok(()) => {}
+            Err(_) => save_to_file(&self.token_file()?, &raw)?,
+        }
+        self.register(true);
+        Ok(())
+    }
+
+    fn save_to_keyring(&self, raw: &str) -> Result<(), StoreError> {
+        Ok(self.entry()?.set_password(raw)?)
+    }
+
+    /// Returns [None] when nothing is stored or the stored value is unreadable
+    pub fn load(&self) -> Option<StoredToken> {
+        let raw = self
+            .entry()
+            .ok()
+            .and_then(|entry| entry.get_password().ok())
+            .or_else(|| fs::read_to_string(self.token_file().ok()?).ok())?;
+        serde_json::from_str(&raw).ok()
+    }
+
+    /// Same as [Self::save], run on the blocking thread pool
+    /// so the credential store does not stall the async runtime
+    pub async fn save_async(&self, resp: &AuthenticationResponse) -> Result<(), StoreError> {
+        let store = self.clone();
+        let resp = resp.clone();
+        run_blocking(move || store.save(&resp)).await
+    }
+
+    /// Same as [Self::load], run on the blocking thread pool
+    pub async fn load_async(&self) -> Option<StoredToken> {
+        let store = self.clone();
+        run_blocking(move || store.load()).await
+    }
+
+    /// Deletes the stored token from both the keyring and the fallback file.
+    /// Clearing an empty store is not an error. Failing to reach the keyring
+    /// is only logged so the fallback file still gets removed.
+    pub fn clear(&self) -> Result<(), StoreError> {
+        self.clear_from(self.use_keyring.then(|| self.entry()))
+    }
+
+    fn clear_from(
+        &self,
+        entry: Option<Result<keyring::Entry, StoreError>>,
+    ) -> Result<(), StoreError> {
+        match entry.map(|entry| Ok(entry?.delete_password()?)) {
+            None
+            | Some(Ok(()))
+            | Some(Err(StoreError::KeyringError(keyring::Error::NoEntry)))
+            | Some(Err(StoreError::KeyringError(keyring::Error::NoStorageAccess(_)))) => {}
+            Some(Err(e)) => tracing::warn!("failed to delete the token from the keyring: {}", e),
+        }
+        if let Some(dir) = &self.config_dir {
+            match fs::remove_file(dir.join(self.token_file_name())) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.register(false);
+        Ok(())
+    }
+}
+
+async fn run_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+fn save_to_file(path: &Path, raw: &str) -> Result<(), StoreError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(raw.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn stored_token_expires_at_an_absolute_time() {
+        let resp = testing::auth_response("access", "refresh");
+        let received_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let token = StoredToken::from_response(&resp, received_at);
+        assert_eq!(token.expires_at, 1_700_003_600);
+
+        let raw = serde_json::to_string(&token).unwrap();
+        assert!(raw.contains(r#""expires_at":1700003600"#), "{}", raw);
+        assert_eq!(serde_json::from_str::<StoredToken>(&raw).unwrap(), token);
+    }
+
+    #[test]
+    fn file_fallback_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = testing::token_store(dir.path());
+        assert_eq!(store.load(), None);
+
+        store
+            .save(&testing::auth_response("access", "refresh"))
+            .unwrap();
+        let path = dir.path().join(TOKEN_FILE);
+        assert!(path.exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let token = store.load().unwrap();
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.refresh_token, "refresh");
+
+        store.clear().unwrap();
+        assert!(!path.exists());
+        assert_eq!(store.load(), None);
+    }
+
+    #[test]
+    fn keyring_failure_still_clears_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = testing::token_store(dir.path());
+        store
+            .save(&testing::auth_response("access", "refresh"))
+            .unwrap();
+        let credential = keyring::mock::default_credential_builder()
+            .build(None, KEYRING_SERVICE, DEFAULT_ACCOUNT)
+            .unwrap();
+        let entry = keyring::Entry::new_with_credential(credential);
+        let mock: &keyring::mock::MockCredential = entry.get_credential().downcast_ref().unwrap();
+        mock.set_error(keyring::Error::PlatformFailure("locked".into()));
+
+        store.clear_from(Some(Ok(entry))).unwrap();
+        assert!(!dir.path().join(TOKEN_FILE).exists());
+        assert_eq!(store.load(), None);
+        assert!(store.profiles().is_empty());
+    }
+
+    #[test]
+    fn unreadable_file_loads_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(TOKEN_FILE), "not json").unwrap();
+        assert_eq!(testing::token_store(dir.path()).load(), None);
+    }
+
+    fn token_expiring_at(expires_at: u64) -> StoredToken {
+        StoredToken {
+            access_token: "access".to_owned(),
+            refresh_token: "refresh".to_owned(),
+            expires_at,
+            scope: String::new(),
+            id_token: None,
+        }
+    }
+
+    #[test]
+    fn token_is_not_expired_before_the_skew() {
+        let token = token_expiring_at(1_000);
+        let now = UNIX_EPOCH + Duration::from_secs(1_000 - 61);
+        assert!(!token.is_expired_with_skew(now, Duration::from_secs(60)));
+        assert!(!token.is_expired(now));
+    }
+
+    #[test]
+    fn token_is_expired_within_the_skew() {
+        let token = token_expiring_at(1_000);
+        let now = UNIX_EPOCH + Duration::from_secs(1_000 - 60);
+        assert!(token.is_expired_with_skew(now, Duration::from_secs(60)));
+        assert!(token.is_expired(now));
+        assert!(!token.is_expired_with_skew(now, Duration::ZERO));
+    }
+
+    #[test]
+    fn token_is_expired_once_past_its_expiry() {
+        let token = token_expiring_at(1_000);
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        assert!(token.is_expired_with_skew(now, Duration::ZERO));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn async_round_trip_on_a_current_thread_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = testing::token_store(dir.path());
+        let resp = testing::auth_response("access", "refresh");
+
+        let round_trip = async {
+            store.save_async(&resp).await.unwrap();
+            store.load_async().await
+        };
+        let token = tokio::time::timeout(std::time::Duration::from_secs(10), round_trip)
+            .await
+            .expect("the store blocked the runtime")
+            .unwrap();
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.refresh_token, "refresh");
+    }
+
+    #[test]
+    fn profiles_are_stored_independently() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = |account| {
+            TokenStore::new(account)
+                .with_config_dir(dir.path())
+                .without_keyring()
+        };
+        let (work, home) = (store("work"), store("home"));
+        work.save(&testing::auth_response("work-access", "work-refresh"))
+            .unwrap();
+        home.save(&testing::auth_response("home-access", "home-refresh"))
+            .unwrap();
+
+        assert_eq!(work.load().unwrap().access_token, "work-access");
+        assert_eq!(home.load().unwrap().access_token, "home-access");
+        assert_eq!(store("work").load().unwrap().refresh_token, "work-refresh");
+        assert_eq!(testing::token_store(dir.path()).load(), None);
+        assert_eq!(work.profiles(), vec!["work".to_owned(), "home".to_owned()]);
+
+        work.clear().unwrap();
+        assert_eq!(work.load(), None);
+        assert_eq!(home.load().unwrap().access_token, "home-access");
+        assert_eq!(home.profiles(), vec!["home".to_owned()]);
+    }
+}
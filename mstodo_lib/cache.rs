@@ -0,0 +1,239 @@
+//! Local copy of tasks kept in a SQLite database, so they can be read
+//! without waiting on (or having) the network.
+//! Tasks are stored as their JSON representation.
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::{error::Error, models::TodoTask};
+
+/// Statements bringing the schema from version `i` to `i + 1`,
+/// the current version being kept in `PRAGMA user_version`
+const MIGRATIONS: &[&str] = &["CREATE TABLE tasks (
+        list_id TEXT NOT NULL,
+        id TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (list_id, id)
+    )"];
+
+pub struct Cache {
+    connection: Connection,
+}
+
+impl Cache {
+    /// Opens or creates the database at `path`, upgrading its schema if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// A cache that only lives as long as the returned value
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, Error> {
+        let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = connection.unchecked_transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+        Ok(Self { connection })
+    }
+
+    /// Inserts the tasks of a list, overwriting the ones already cached
+    pub fn upsert_tasks(&self, list_id: &str, tasks: &[TodoTask]) -> Result<(), Error> {
+        let tx = self.connection.unchecked_transaction()?;
+        insert_tasks(&tx, list_id, tasks)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replaces every cached task of a list with `tasks`
+    pub fn replace_tasks(&self, list_id: &str, tasks: &[TodoTask]) -> Result<(), Error> {
+        let tx = self.connection.unchecked_transaction()?;
+        tx.execute("DELETE FROM tasks WHERE list_id = ?1", params![list_id])?;
+        insert_tasks(&tx, list_id, tasks)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Cached tasks of a list, empty if it was never cached
+    pub fn get_tasks(&self, list_id: &str) -> Result<Vec<TodoTask>, Error> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT data FROM tasks WHERE list_id = ?1 ORDER BY rowid")?;
+        let rows = statement.query_map(params![list_id], |row| row.get::<_, String>(0))?;
+        let mut tasks = Vec::new();
+        for data in rows {
+            tasks.push(serde_json::from_str(&data?)?);
+        }
+        Ok(tasks)
+    }
+}
+
+fn insert_tasks(connection: &Connection, list_id: &str, tasks: &[TodoTask]) -> Result<(), Error> {
+    let mut statement = connection
+        .prepare("INSERT OR REPLACE INTO tasks (list_id, id, data) VALUES (?1, ?2, ?3)")?;
+    for task in tasks {
+        statement.execute(params![list_id, task.id, serde_json::to_string(task)?])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::testing;
+
+    fn task(id: &str, title: &str) -> TodoTask {
+        serde_json::from_value(testing::task_json(id, title)).unwrap()
+    }
+
+    fn user_version(path: &Path) -> usize {
+        Connection::open(path)
+            .unwrap()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn upserted_tasks_are_read_back() {
+        let cache = Cache::in_memory().unwrap();
+        cache
+            .upsert_tasks("list", &[task("t1", "Buy milk"), task("t2", "Pay rent")])
+            .unwrap();
+        cache
+            .upsert_tasks("list", &[task("t1", "Buy oat milk")])
+            .unwrap();
+        cache
+            .upsert_tasks("other", &[task("t3", "Call mum")])
+            .unwrap();
+
+        let mut tasks = cache.get_tasks("list").unwrap();
+        tasks.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(
+            tasks,
+            vec![task("t1", "Buy oat milk"), task("t2", "Pay rent")]
+        );
+        assert_eq!(
+            cache.get_tasks("other").unwrap(),
+            vec![task("t3", "Call mum")]
+        );
+        assert!(cache.get_tasks("never cached").unwrap().is_empty());
+
+        cache
+            .replace_tasks("list", &[task("t4", "Water plants")])
+            .unwrap();
+        assert_eq!(
+            cache.get_tasks("list").unwrap(),
+            vec![task("t4", "Water plants")]
+        );
+    }
+
+    #[test]
+    fn schema_is_migrated_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.db");
+        // a database created before the cache existed
+        Connection::open(&path).unwrap();
+        assert_eq!(user_version(&path), 0);
+
+        let cache = Cache::open(&path).unwrap();
+        cache
+            .upsert_tasks("list", &[task("t1", "Buy milk")])
+            .unwrap();
+        drop(cache);
+        assert_eq!(user_version(&path), MIGRATIONS.len());
+
+        // reopening an up to date database runs no migration and keeps the tasks
+        let cache = Cache::open(&path).unwrap();
+        assert_eq!(
+            cache.get_tasks("list").unwrap(),
+            vec![task("t1", "Buy milk")]
+        );
+        assert_eq!(user_version(&path), MIGRATIONS.len());
+    }
+
+    /// Cache holding `t1`, and a server answering with `t2` after `delay`
+    async fn slow_server(delay: Duration) -> (MockServer, Arc<Mutex<Cache>>) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/list/tasks"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "value": [testing::task_json("t2", "Pay rent")],
+                    }))
+                    .set_delay(delay),
+            )
+            .mount(&server)
+            .await;
+        let cache = Cache::in_memory().unwrap();
+        cache
+            .upsert_tasks("list", &[task("t1", "Buy milk")])
+            .unwrap();
+        (server, Arc::new(Mutex::new(cache)))
+    }
+
+    #[tokio::test]
+    async fn cached_tasks_are_returned_without_waiting_on_the_server() {
+        let (server, cache) = slow_server(Duration::from_secs(2)).await;
+        let dir = tempfile::tempdir().unwrap();
+        let client = Arc::new(testing::client(&server, dir.path()));
+
+        let (cached, refresh) = tokio::time::timeout(
+            Duration::from_millis(500),
+            client.list_tasks_cached("list", &cache),
+        )
+        .await
+        .expect("waited on the server")
+        .unwrap();
+        assert_eq!(cached, vec![task("t1", "Buy milk")]);
+
+        assert_eq!(
+            refresh.await.unwrap().unwrap(),
+            vec![task("t2", "Pay rent")]
+        );
+        assert_eq!(
+            cache.lock().unwrap().get_tasks("list").unwrap(),
+            vec![task("t2", "Pay rent")]
+        );
+    }
+
+    #[tokio::test]
+    async fn timed_out_refresh_resolves_to_the_cached_tasks() {
+        let (server, cache) = slow_server(Duration::from_secs(2)).await;
+        let dir = tempfile::tempdir().unwrap();
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let client = Arc::new(
+            testing::client_builder(&server)
+                .http_client(http_client)
+                .build(testing::token_provider(&server, dir.path())),
+        );
+
+        let (_, refresh) = client.list_tasks_cached("list", &cache).await.unwrap();
+        assert_eq!(
+            refresh.await.unwrap().unwrap(),
+            vec![task("t1", "Buy milk")]
+        );
+        assert_eq!(
+            cache.lock().unwrap().get_tasks("list").unwrap(),
+            vec![task("t1", "Buy milk")]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::Arc;
+
+use super::{
+    MetricsObserver, NoopObserver, TodoClient, DEFAULT_CONCURRENCY_LIMIT,
+    DEFAULT_MAX_THROTTLE_RETRIES, GRAPH_BASE_URL, WRITE_SCOPE,
+};
+use crate::auth::TokenProvider;
+
+/// Version of the Graph API requests are sent to
+/// See "https://learn.microsoft.com/en-us/graph/versioning-and-support"
+/// for more information
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// `v1.0`, the generally available API
+    #[default]
+    V1,
+    /// `beta`, with features that may still change or be removed
+    Beta,
+}
+
+impl ApiVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1.0",
+            ApiVersion::Beta => "beta",
+        }
+    }
+}
+
+/// Builds a [TodoClient] for another Graph deployment or with non-default settings.
+/// Every value defaults to the one used by [TodoClient::with_token_provider].
+#[derive(Clone)]
+pub struct TodoClientBuilder {
+    graph_base_url: String,
+    api_version: ApiVersion,
+    http_client: Option<reqwest::Client>,
+    default_headers: reqwest::header::HeaderMap,
+    page_size: Option<u32>,
+    max_throttle_retries: u32,
+    concurrency_limit: usize,
+    require_scopes: bool,
+    dry_run: bool,
+    metrics: Arc<dyn MetricsObserver + Send + Sync>,
+}
+
+impl Default for TodoClientBuilder {
+    fn default() -> Self {
+        Self {
+            graph_base_url: GRAPH_BASE_URL.to_owned(),
+            api_version: ApiVersion::default(),
+            http_client: None,
+            default_headers: crate::utils::default_headers(),
+            page_size: None,
+            max_throttle_retries: DEFAULT_MAX_THROTTLE_RETRIES,
+            concurrency_limit: DEFAULT_CONCURRENCY_LIMIT,
+            require_scopes: false,
+            dry_run: false,
+            metrics: Arc::new(NoopObserver),
+        }
+    }
+}
+
+impl TodoClientBuilder {
+    /// Root of the Graph API, `https://graph.microsoft.com` by default,
+    /// followed by the [ApiVersion] and then every request path.
+    /// National clouds are served by other hosts:
+    /// - US Government L4 (GCC High): `https://graph.microsoft.us`
+    /// - US Government L5 (DoD): `https://dod-graph.microsoft.us`
+    /// - China (21Vianet): `https://microsoftgraph.chinacloudapi.cn`
+    ///
+    /// See "https://learn.microsoft.com/en-us/graph/deployments"
+    /// for more information
+    pub fn graph_base_url(mut self, graph_base_url: &str) -> Self {
+        self.graph_base_url = graph_base_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    pub fn api_version(mut self, api_version: ApiVersion) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    /// Uses a pre-configured client (proxies, TLS, timeouts...) instead of a default one
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Headers added to every request, replacing the defaults with the same name
+    /// such as `User-Agent: mstodo/<version>`
+    pub fn default_headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    /// See [TodoClient::with_page_size]
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// See [TodoClient::with_max_throttle_retries]
+    pub fn max_throttle_retries(mut self, max_throttle_retries: u32) -> Self {
+        self.max_throttle_retries = max_throttle_retries;
+        self
+    }
+
+    /// See [TodoClient::with_concurrency_limit]
+    pub fn concurrency_limit(mut self, concurrency_limit: usize) -> Self {
+        self.concurrency_limit = concurrency_limit.max(1);
+        self
+    }
+
+    /// See [TodoClient::with_require_scopes]
+    pub fn require_scopes(mut self, require_scopes: bool) -> Self {
+        self.require_scopes = require_scopes;
+        self
+    }
+
+    /// See [TodoClient::with_dry_run]
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Told about every HTTP call, see [MetricsObserver]
+    pub fn metrics_observer(
+        mut self,
+        observer: impl MetricsObserver + Send + Sync + 'static,
+    ) -> Self {
+        self.metrics = Arc::new(observer);
+        self
+    }
+
+    pub fn build(self, tokens: TokenProvider) -> TodoClient {
+        if !tokens.has_scope(WRITE_SCOPE) {
+            tracing::warn!(
+                "token lacks the {} scope, changes to tasks will be rejected",
+                WRITE_SCOPE
+            );
+        }
+        TodoClient {
+            http_client: self.http_client.unwrap_or_default(),
+            default_headers: self.default_headers,
+            tokens,
+            graph_base_url: format!("{}/{}", self.graph_base_url, self.api_version.as_str()),
+            page_size: self.page_size,
+            max_throttle_retries: self.max_throttle_retries,
+            concurrency_limit: self.concurrency_limit,
+            require_scopes: self.require_scopes,
+            dry_run: self.dry_run,
+            metrics: self.metrics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn requests_go_to_the_graph_base_url() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "value": [testing::list_json("l1", "Tasks")],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // a trailing slash does not end up doubled
+        let client = TodoClient::builder()
+            .graph_base_url(&format!("{}/", server.uri()))
+            .build(testing::token_provider(&server, dir.path()));
+
+        assert_eq!(client.list_task_lists().await.unwrap()[0].id, "l1");
+    }
+
+    #[test]
+    fn commercial_cloud_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let authenticator = crate::auth::DeviceCodeAuthentication::builder()
+            .token_store(testing::token_store(dir.path()))
+            .build()
+            .unwrap();
+        let tokens = TokenProvider::new(testing::auth_response("access", "refresh"), authenticator);
+        let client = TodoClient::builder().build(tokens);
+        assert_eq!(client.graph_base_url, "https://graph.microsoft.com/v1.0");
+    }
+
+    #[tokio::test]
+    async fn beta_requests_go_to_the_beta_endpoint() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/beta/me/todo/lists"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "value": [] })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client_builder(&server)
+            .api_version(ApiVersion::Beta)
+            .build(testing::token_provider(&server, dir.path()));
+
+        assert!(client.graph_base_url.ends_with("/beta"));
+        assert!(client.list_task_lists().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn user_agent_is_sent_unless_overridden() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let lists = ResponseTemplate::new(200).set_body_json(serde_json::json!({ "value": [] }));
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .and(wiremock::matchers::header(
+                "User-Agent",
+                concat!("mstodo/", env!("CARGO_PKG_VERSION")),
+            ))
+            .respond_with(lists.clone())
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .and(wiremock::matchers::header("User-Agent", "my-app/2.0"))
+            .respond_with(lists)
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = testing::client(&server, dir.path());
+        client.list_task_lists().await.unwrap();
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::USER_AGENT,
+            reqwest::header::HeaderValue::from_static("my-app/2.0"),
+        );
+        let client = testing::client_builder(&server)
+            .default_headers(headers)
+            .build(testing::token_provider(&server, dir.path()));
+        client.list_task_lists().await.unwrap();
+    }
+}
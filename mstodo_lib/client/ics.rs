@@ -0,0 +1,143 @@
+//! Conversion of tasks to iCalendar VTODO components.
+//! See "https://datatracker.ietf.org/doc/html/rfc5545#section-3.6.2"
+//! for more information
+use std::{io::Write, time::SystemTime};
+
+use crate::error::Error;
+use crate::models::{DateTimeTimeZone, TaskStatus, TodoTask, UTC};
+
+/// Content lines longer than this many bytes are folded
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Writes a VCALENDAR holding one VTODO per task
+pub(super) fn write_calendar(tasks: &[TodoTask], mut writer: impl Write) -> Result<(), Error> {
+    let stamp = format!("{}Z", compact(&crate::utils::format_utc(SystemTime::now())));
+    write_line(&mut writer, "BEGIN:VCALENDAR")?;
+    write_line(&mut writer, "VERSION:2.0")?;
+    write_line(&mut writer, "PRODID:-//mstodo//mstodo//EN")?;
+    for task in tasks {
+        write_line(&mut writer, "BEGIN:VTODO")?;
+        write_line(&mut writer, &format!("UID:{}", escape(&task.id)))?;
+        write_line(&mut writer, &format!("DTSTAMP:{}", stamp))?;
+        write_line(&mut writer, &format!("SUMMARY:{}", escape(&task.title)))?;
+        if let Some(body) = &task.body {
+            write_line(
+                &mut writer,
+                &format!("DESCRIPTION:{}", escape(&body.content)),
+            )?;
+        }
+        if let Some(due) = &task.due_date_time {
+            write_line(&mut writer, &format!("DUE{}", date_time(due)))?;
+        }
+        write_line(&mut writer, &format!("STATUS:{}", status(&task.status)))?;
+        if task.status == TaskStatus::Completed {
+            if let Some(completed) = &task.completed_date_time {
+                write_line(&mut writer, &format!("COMPLETED{}", date_time(completed)))?;
+            }
+        }
+        write_line(&mut writer, "END:VTODO")?;
+    }
+    write_line(&mut writer, "END:VCALENDAR")?;
+    Ok(())
+}
+
+fn status(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::InProgress => "IN-PROCESS",
+        TaskStatus::Completed => "COMPLETED",
+        _ => "NEEDS-ACTION",
+    }
+}
+
+/// Value of a date-time property including the `:` separator,
+/// e.g. `:20240501T000000Z` or `;TZID=Europe/Paris:20240501T000000`
+fn date_time(date_time: &DateTimeTimeZone) -> String {
+    let value = compact(&date_time.date_time);
+    if date_time.time_zone == UTC {
+        format!(":{}Z", value)
+    } else {
+        format!(";TZID={}:{}", date_time.time_zone, value)
+    }
+}
+
+/// Turns `2024-05-01T00:00:00.0000000` into `20240501T000000`
+fn compact(date_time: &str) -> String {
+    date_time
+        .chars()
+        .take("YYYY-MM-DDTHH:MM:SS".len())
+        .filter(|c| *c != '-' && *c != ':')
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Writes a content line, folding it as required by RFC 5545
+fn write_line(writer: &mut impl Write, line: &str) -> std::io::Result<()> {
+    let mut rest = line;
+    let mut limit = MAX_LINE_LENGTH;
+    while rest.len() > limit {
+        let mut split = limit;
+        while !rest.is_char_boundary(split) {
+            split -= 1;
+        }
+        write!(writer, "{}\r\n ", &rest[..split])?;
+        rest = &rest[split..];
+        // the leading space of continuation lines counts towards their length
+        limit = MAX_LINE_LENGTH - 1;
+    }
+    write!(writer, "{}\r\n", rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn vtodo_of_a_task_with_a_due_date() {
+        let mut task = testing::task_json("t1", "Pay rent");
+        task["body"]["content"] = "Before noon, please".into();
+        task["dueDateTime"] = serde_json::json!({
+            "dateTime": "2024-05-01T00:00:00.0000000",
+            "timeZone": "UTC",
+        });
+        let task: TodoTask = serde_json::from_value(task).unwrap();
+        let mut output = Vec::new();
+        write_calendar(&[task], &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.ends_with("\r\n"));
+        let lines: Vec<&str> = output.split_terminator("\r\n").collect();
+        let stamp = lines
+            .iter()
+            .find(|line| line.starts_with("DTSTAMP:"))
+            .unwrap();
+        assert!(stamp.ends_with('Z'));
+        let lines: Vec<&str> = lines
+            .into_iter()
+            .filter(|line| !line.starts_with("DTSTAMP:"))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "BEGIN:VCALENDAR",
+                "VERSION:2.0",
+                "PRODID:-//mstodo//mstodo//EN",
+                "BEGIN:VTODO",
+                "UID:t1",
+                "SUMMARY:Pay rent",
+                "DESCRIPTION:Before noon\\, please",
+                "DUE:20240501T000000Z",
+                "STATUS:NEEDS-ACTION",
+                "END:VTODO",
+                "END:VCALENDAR",
+            ]
+        );
+    }
+}
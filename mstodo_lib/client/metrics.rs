@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+/// Told about every HTTP call made by a [super::TodoClient], e.g. to record
+/// latencies or count failures. Retries and token refreshes are separate calls.
+/// Both methods do nothing by default.
+pub trait MetricsObserver {
+    /// Called right before a request is sent, `path` being the path of its url
+    fn on_request(&self, _method: &reqwest::Method, _path: &str) {}
+
+    /// Called once a response is received, not when the request fails
+    /// without one, e.g. on a timeout
+    fn on_response(
+        &self,
+        _method: &reqwest::Method,
+        _path: &str,
+        _status: reqwest::StatusCode,
+        _elapsed: Duration,
+    ) {
+    }
+}
+
+/// Observer used when none is set, ignoring everything
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl MetricsObserver for NoopObserver {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::testing;
+
+    #[derive(Default)]
+    struct CountingObserver {
+        requests: AtomicUsize,
+        responses: Mutex<Vec<(String, u16)>>,
+    }
+
+    impl MetricsObserver for Arc<CountingObserver> {
+        fn on_request(&self, _method: &reqwest::Method, _path: &str) {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_response(
+            &self,
+            method: &reqwest::Method,
+            path: &str,
+            status: reqwest::StatusCode,
+            _elapsed: Duration,
+        ) {
+            let call = (format!("{} {}", method, path), status.as_u16());
+            self.responses.lock().unwrap().push(call);
+        }
+    }
+
+    #[tokio::test]
+    async fn observer_is_told_about_every_call() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .and(query_param("$skiptoken", "page2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "value": [] })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::list_json("l1", "Tasks")],
+                "@odata.nextLink": format!("{}/v1.0/me/todo/lists?$skiptoken=page2", server.uri()),
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/l1/tasks/gone"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        let observer = Arc::new(CountingObserver::default());
+        let client = testing::client_builder(&server)
+            .metrics_observer(Arc::clone(&observer))
+            .build(testing::token_provider(&server, dir.path()));
+
+        client.list_task_lists().await.unwrap();
+        client.delete_task("l1", "gone").await.unwrap_err();
+        assert_eq!(observer.requests.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            *observer.responses.lock().unwrap(),
+            vec![
+                ("GET /v1.0/me/todo/lists".to_owned(), 200),
+                ("GET /v1.0/me/todo/lists".to_owned(), 200),
+                ("DELETE /v1.0/me/todo/lists/l1/tasks/gone".to_owned(), 404),
+            ]
+        );
+    }
+}
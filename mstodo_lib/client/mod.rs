@@ -0,0 +1,2590 @@
+//! Client for the Microsoft To Do API exposed by Microsoft Graph.
+//! See "https://learn.microsoft.com/en-us/graph/api/resources/todo-overview"
+//! for more information
+mod builder;
+mod ics;
+mod metrics;
+mod query;
+mod requests;
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use futures::{Stream, StreamExt, TryStreamExt};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+
+/// Kept for paths predating the top-level [crate::models] module
+pub use crate::models;
+use crate::models::*;
+use crate::{
+    auth::{responses::AuthenticationResponse, DeviceCodeAuthentication, TokenProvider},
+    error::{ApiError, AuthenticationError, Error},
+};
+pub use builder::{ApiVersion, TodoClientBuilder};
+pub use metrics::{MetricsObserver, NoopObserver};
+pub use query::TaskQuery;
+
+const GRAPH_BASE_URL: &str = "https://graph.microsoft.com";
+const DEFAULT_MAX_THROTTLE_RETRIES: u32 = 3;
+const DEFAULT_CONCURRENCY_LIMIT: usize = 4;
+/// Scope needed to change tasks and lists
+const WRITE_SCOPE: &str = "Tasks.ReadWrite";
+/// Largest file [TodoClient::add_attachment] uploads inline,
+/// bigger ones needing an upload session
+const MAX_INLINE_ATTACHMENT_SIZE: usize = 3 * 1024 * 1024;
+/// Most sub-requests Graph accepts in a single `$batch` request
+const MAX_BATCH_SIZE: usize = 20;
+/// Wait used when a throttled response does not say how long to wait
+const DEFAULT_THROTTLE_WAIT: Duration = Duration::from_secs(5);
+
+/// An authenticated session with Microsoft Graph.
+/// Every request carries the access token as a bearer token.
+pub struct TodoClient {
+    http_client: reqwest::Client,
+    default_headers: reqwest::header::HeaderMap,
+    tokens: TokenProvider,
+    /// Including the [ApiVersion]
+    graph_base_url: String,
+    page_size: Option<u32>,
+    max_throttle_retries: u32,
+    concurrency_limit: usize,
+    require_scopes: bool,
+    dry_run: bool,
+    metrics: std::sync::Arc<dyn MetricsObserver + Send + Sync>,
+}
+
+/// Outcome of an operation applied to several items, e.g. [TodoClient::complete_tasks],
+/// in the order the items were given
+#[derive(Debug, Default)]
+pub struct BulkResult {
+    /// Ids of the items the operation succeeded for
+    pub succeeded: Vec<String>,
+    /// Ids of the items the operation failed for, with the reason
+    pub failed: Vec<(String, Error)>,
+}
+
+impl TodoClient {
+    pub fn new(auth: AuthenticationResponse) -> Self {
+        Self::with_authenticator(auth, DeviceCodeAuthentication::new())
+    }
+
+    /// `authenticator` is used to refresh the access token once it expires
+    /// or when the server rejects it
+    pub fn with_authenticator(
+        auth: AuthenticationResponse,
+        authenticator: DeviceCodeAuthentication,
+    ) -> Self {
+        Self::with_token_provider(TokenProvider::new(auth, authenticator))
+    }
+
+    pub fn with_token_provider(tokens: TokenProvider) -> Self {
+        Self::builder().build(tokens)
+    }
+
+    pub fn builder() -> TodoClientBuilder {
+        TodoClientBuilder::default()
+    }
+
+    /// Number of items requested per page (`$top`) when listing collections,
+    /// the server default is used otherwise
+    pub fn with_page_size(mut self, page_size: u32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Number of times a throttled (429) request is retried
+    /// before failing with [Error::Throttled]
+    pub fn with_max_throttle_retries(mut self, max_throttle_retries: u32) -> Self {
+        self.max_throttle_retries = max_throttle_retries;
+        self
+    }
+
+    /// Maximum number of requests in flight when fetching several
+    /// collections at once, e.g. in [Self::list_all_tasks]
+    pub fn with_concurrency_limit(mut self, concurrency_limit: usize) -> Self {
+        self.concurrency_limit = concurrency_limit.max(1);
+        self
+    }
+
+    /// When set, changes to tasks and lists fail with [Error::MissingScope]
+    /// before being sent if the token lacks `Tasks.ReadWrite`,
+    /// instead of being rejected by the server
+    pub fn with_require_scopes(mut self, require_scopes: bool) -> Self {
+        self.require_scopes = require_scopes;
+        self
+    }
+
+    /// When set, requests other than `GET` are not sent but logged,
+    /// and fail with [Error::DryRun] describing them
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Authenticates with [DeviceCodeAuthentication::authenticate]
+    /// and creates a client from the resulting token
+    pub async fn login() -> Result<Self, Error> {
+        Self::login_with(DeviceCodeAuthentication::new()).await
+    }
+
+    /// Same as [Self::login] with a custom authenticator, e.g. for another profile
+    pub async fn login_with(authenticator: DeviceCodeAuthentication) -> Result<Self, Error> {
+        Ok(Self::with_token_provider(
+            TokenProvider::login(authenticator).await?,
+        ))
+    }
+
+    /// Starts an authenticated request to `path`, relative to the Graph API root
+    /// e.g. `/me/todo/lists`, see [TodoClientBuilder::graph_base_url]
+    pub fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.request_url(method, &format!("{}{}", self.graph_base_url, path))
+    }
+
+    fn request_url(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        self.http_client
+            .request(method, url)
+            .headers(self.default_headers.clone())
+            .bearer_auth(self.tokens.current().access_token)
+    }
+
+    fn check_write_scope(&self, url: &reqwest::Url) -> Result<(), Error> {
+        if !url.path().contains("/me/todo/") {
+            return Ok(());
+        }
+        if self.tokens.has_scope(WRITE_SCOPE) {
+            Ok(())
+        } else {
+            Err(Error::MissingScope(WRITE_SCOPE.to_owned()))
+        }
+    }
+
+    /// Sends a request, waiting and retrying as told by `Retry-After`
+    /// when the server throttles it. When the access token is rejected,
+    /// it is refreshed once and the request replayed with the new one.
+    /// Requests with a streamed body cannot be replayed, so they fail with
+    /// the [Error::Api] of the rejection instead, the next ones using the new token.
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response, Error> {
+        let mut retries = 0;
+        let mut refreshed = false;
+        loop {
+            let Some(attempt) = req.try_clone() else {
+                let resp = self.execute(self.prepare(req.build()?).await?).await?;
+                let status = resp.status();
+                if status == reqwest::StatusCode::UNAUTHORIZED {
+                    self.tokens.refresh().await?;
+                    let body = resp.text().await?;
+                    let api_error = ApiError::from_body(status.as_u16(), &body)
+                        .unwrap_or_else(|| ApiError::unparsed(status, body));
+                    return Err(Error::Api(api_error));
+                }
+                return Ok(resp);
+            };
+            let attempt = self.prepare(attempt.build()?).await?;
+            let resp = self.execute(attempt).await?;
+            match resp.status() {
+                reqwest::StatusCode::UNAUTHORIZED if refreshed => {
+                    return Err(AuthenticationError::AuthenticationFailed.into())
+                }
+                reqwest::StatusCode::UNAUTHORIZED => {
+                    self.tokens.refresh().await?;
+                    refreshed = true;
+                    continue;
+                }
+                reqwest::StatusCode::TOO_MANY_REQUESTS => {}
+                _ => return Ok(resp),
+            }
+            let wait = crate::utils::retry_after(resp.headers()).unwrap_or(DEFAULT_THROTTLE_WAIT);
+            if retries >= self.max_throttle_retries {
+                return Err(Error::Throttled(wait));
+            }
+            tracing::debug!("throttled, retrying in {:?}", wait);
+            tokio::time::sleep(wait).await;
+            retries += 1;
+        }
+    }
+
+    /// Checks a request is allowed to be sent and sets the current access token.
+    /// Fails with [Error::DryRun] for changes in dry-run mode.
+    async fn prepare(&self, mut req: reqwest::Request) -> Result<reqwest::Request, Error> {
+        if self.require_scopes && req.method() != Method::GET {
+            self.check_write_scope(req.url())?;
+        }
+        if self.dry_run && req.method() != Method::GET {
+            let description = describe(&req);
+            tracing::info!("dry run, not sending {}", description);
+            return Err(Error::DryRun(description));
+        }
+        // the token may have been refreshed since the request was built
+        let authorization = format!("Bearer {}", self.tokens.access_token().await?)
+            .parse()
+            .map_err(|_| AuthenticationError::AuthenticationFailed)?;
+        req.headers_mut()
+            .insert(reqwest::header::AUTHORIZATION, authorization);
+        Ok(req)
+    }
+
+    /// Sends a request as is, reporting it to the [MetricsObserver]
+    async fn execute(&self, req: reqwest::Request) -> Result<reqwest::Response, Error> {
+        let method = req.method().clone();
+        let path = req.url().path().to_owned();
+        self.metrics.on_request(&method, &path);
+        let start = std::time::Instant::now();
+        let resp = self.http_client.execute(req).await?;
+        self.metrics
+            .on_response(&method, &path, resp.status(), start.elapsed());
+        Ok(resp)
+    }
+
+    /// Fetches every item of a collection, following `@odata.nextLink` until the last page
+    async fn get_all<T: DeserializeOwned>(&self, path: &str) -> Result<Vec<T>, Error> {
+        self.get_all_with_query(path, &[]).await
+    }
+
+    /// Same as [Self::get_all], with additional OData query options.
+    /// A `$top` among them takes precedence over the page size.
+    async fn get_all_with_query<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<Vec<T>, Error> {
+        let req = self.first_page_request(path, query);
+        let mut page: GraphCollection<T> = parse_response(self.send(req).await?).await?;
+        let mut items = Vec::new();
+        loop {
+            items.append(&mut page.value);
+            match page.next_link {
+                // next links already carry the original query
+                Some(link) => {
+                    let req = self.request_url(Method::GET, &link);
+                    let resp = self.send(req).await?;
+                    page = parse_response(resp).await?;
+                }
+                None => break Ok(items),
+            }
+        }
+    }
+
+    /// Same as [Self::get_all_with_query], yielding the items as the pages arrive
+    fn stream_all<'a, T: DeserializeOwned + 'a>(
+        &'a self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> impl Stream<Item = Result<T, Error>> + 'a {
+        let first = self.first_page_request(path, query);
+        futures::stream::try_unfold(Some(first), move |req| async move {
+            let Some(req) = req else {
+                return Ok(None);
+            };
+            let page: GraphCollection<T> = parse_response(self.send(req).await?).await?;
+            let next = page
+                .next_link
+                .map(|link| self.request_url(Method::GET, &link));
+            let items = futures::stream::iter(page.value.into_iter().map(Ok::<T, Error>));
+            Ok::<_, Error>(Some((items, next)))
+        })
+        .try_flatten()
+    }
+
+    /// Request for the first page of a collection, asking for
+    /// [Self::with_page_size] items unless `query` has a `$top`
+    fn first_page_request(&self, path: &str, query: &[(&str, String)]) -> reqwest::RequestBuilder {
+        let mut req = self.request(Method::GET, path).query(query);
+        if let Some(page_size) = self.page_size {
+            if !query.iter().any(|(key, _)| *key == "$top") {
+                req = req.query(&[("$top", page_size)]);
+            }
+        }
+        req
+    }
+
+    /// GETs an endpoint this crate does not wrap, e.g.
+    /// `/me/todo/lists/{id}/tasks/{id}/attachments`, relative to the Graph API root.
+    /// Use [serde_json::Value] as `T` for the raw JSON.
+    pub async fn raw_get<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        self.raw_request(Method::GET, path, None).await
+    }
+
+    /// Same as [Self::raw_get] with any method and an optional JSON body.
+    /// An empty response body is read as JSON `null`.
+    pub async fn raw_request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, Error> {
+        let mut req = self.request(method, path);
+        if let Some(body) = body {
+            req = req.json(body);
+        }
+        let resp = check_response(self.send(req).await?).await?;
+        let text = resp.text().await?;
+        if text.trim().is_empty() {
+            return Ok(serde_json::from_value(serde_json::Value::Null)?);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Fetches the master category list of the signed-in user.
+    /// Requires the `MailboxSettings.Read` scope.
+    pub async fn list_categories(&self) -> Result<Vec<OutlookCategory>, Error> {
+        self.get_all("/me/outlook/masterCategories").await
+    }
+
+    /// Subscribes to change notifications, see [SubscriptionRequest::for_list]
+    pub async fn create_subscription(
+        &self,
+        subscription: SubscriptionRequest,
+    ) -> Result<Subscription, Error> {
+        let req = self
+            .request(Method::POST, "/subscriptions")
+            .json(&subscription);
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    /// Pushes back the expiry of a subscription, e.g. `2024-05-01T00:00:00Z`
+    pub async fn renew_subscription(
+        &self,
+        subscription_id: &str,
+        expiration_date_time: &str,
+    ) -> Result<Subscription, Error> {
+        let req = self
+            .request(
+                Method::PATCH,
+                &format!("/subscriptions/{}", subscription_id),
+            )
+            .json(&requests::SubscriptionRenewalRequest {
+                expiration_date_time,
+            });
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    pub async fn delete_subscription(&self, subscription_id: &str) -> Result<(), Error> {
+        let req = self.request(
+            Method::DELETE,
+            &format!("/subscriptions/{}", subscription_id),
+        );
+        let resp = self.send(req).await?;
+        check_response(resp).await?;
+        Ok(())
+    }
+
+    /// Sends `requests` through the `$batch` endpoint, 20 at a time.
+    /// Responses are returned in the same order as `requests`.
+    /// See "https://learn.microsoft.com/en-us/graph/json-batching"
+    /// for more information
+    pub async fn batch(&self, requests: Vec<BatchRequest>) -> Result<Vec<BatchResponse>, Error> {
+        let mut responses = Vec::with_capacity(requests.len());
+        for chunk in requests.chunks(MAX_BATCH_SIZE) {
+            let body = requests::BatchRequestBody {
+                requests: chunk
+                    .iter()
+                    .enumerate()
+                    .map(|(i, request)| requests::BatchItem::new(i.to_string(), request))
+                    .collect(),
+            };
+            let req = self.request(Method::POST, "/$batch").json(&body);
+            let resp = self.send(req).await?;
+            let mut chunk_responses: BatchResponseBody = parse_response(resp).await?;
+            for i in 0..chunk.len() {
+                let id = i.to_string();
+                let position = chunk_responses
+                    .responses
+                    .iter()
+                    .position(|response| response.id == id)
+                    .ok_or_else(|| {
+                        Error::UnexpectedResponse(format!("no response to batch request {}", id))
+                    })?;
+                responses.push(chunk_responses.responses.swap_remove(position));
+            }
+        }
+        Ok(responses)
+    }
+
+    /// Fetches the signed-in user, also a cheap way to check the token works
+    pub async fn me(&self) -> Result<User, Error> {
+        let req = self.request(Method::GET, "/me");
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    /// Time taken by the cheapest authenticated request, `/me` with only the id.
+    /// Fails with [Error::Auth] when the token is rejected
+    /// and [Error::Http] when Graph cannot be reached.
+    pub async fn ping(&self) -> Result<Duration, Error> {
+        let req = self.request(Method::GET, "/me").query(&[("$select", "id")]);
+        let start = std::time::Instant::now();
+        let resp = self.send(req).await?;
+        check_response(resp).await?;
+        Ok(start.elapsed())
+    }
+
+    /// Fetches every task list of the signed-in user
+    pub async fn list_task_lists(&self) -> Result<Vec<TodoTaskList>, Error> {
+        self.get_all("/me/todo/lists").await
+    }
+
+    /// Fetches the built-in "Tasks" list, the one whose
+    /// `well_known_list_name` is `defaultList`
+    pub async fn default_task_list(&self) -> Result<TodoTaskList, Error> {
+        self.list_task_lists()
+            .await?
+            .into_iter()
+            .find(|list| list.well_known_list_name.is_default())
+            .ok_or_else(|| Error::NoMatch("no default task list".to_owned()))
+    }
+
+    /// Finds a task list by id or display name.
+    /// `default` stands for [Self::default_task_list].
+    pub async fn resolve_list(&self, name_or_id: &str) -> Result<TodoTaskList, Error> {
+        if name_or_id == "default" {
+            return self.default_task_list().await;
+        }
+        let lists = self.list_task_lists().await?;
+        find_list(lists, name_or_id)
+            .ok_or_else(|| Error::NoMatch(format!("no task list named {}", name_or_id)))
+    }
+
+    /// Creates a task list, the returned list carries its server-assigned id
+    pub async fn create_task_list(&self, display_name: &str) -> Result<TodoTaskList, Error> {
+        if display_name.trim().is_empty() {
+            return Err(Error::InvalidInput(
+                "task list name must not be empty".to_owned(),
+            ));
+        }
+        let req = self
+            .request(Method::POST, "/me/todo/lists")
+            .json(&requests::TaskListRequest { display_name });
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    pub async fn update_task_list(
+        &self,
+        list_id: &str,
+        new_name: &str,
+    ) -> Result<TodoTaskList, Error> {
+        if new_name.trim().is_empty() {
+            return Err(Error::InvalidInput(
+                "task list name must not be empty".to_owned(),
+            ));
+        }
+        let req = self
+            .request(Method::PATCH, &format!("/me/todo/lists/{}", list_id))
+            .json(&requests::TaskListRequest {
+                display_name: new_name,
+            });
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    pub async fn delete_task_list(&self, list_id: &str) -> Result<(), Error> {
+        let req = self.request(Method::DELETE, &format!("/me/todo/lists/{}", list_id));
+        let resp = self.send(req).await?;
+        check_response(resp).await?;
+        Ok(())
+    }
+
+    /// Fetches a single task list
+    pub async fn get_task_list(&self, list_id: &str) -> Result<TodoTaskList, Error> {
+        let req = self.request(Method::GET, &format!("/me/todo/lists/{}", list_id));
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    /// Fetches the people a list is shared with, none for a list that is not shared.
+    /// Graph does not document list sharing, members are read from
+    /// `allowedAudience` and a server without it is taken as there being none.
+    pub async fn list_members(&self, list_id: &str) -> Result<Vec<ListMember>, Error> {
+        if !self.get_task_list(list_id).await?.is_shared {
+            return Ok(Vec::new());
+        }
+        let path = format!("/me/todo/lists/{}/allowedAudience", list_id);
+        match self.get_all(&path).await {
+            Err(Error::NotFound(_)) => Ok(Vec::new()),
+            members => members,
+        }
+    }
+
+    /// Fetches every task of a task list
+    pub async fn list_tasks(&self, list_id: &str) -> Result<Vec<TodoTask>, Error> {
+        self.list_tasks_with_query(list_id, &TaskQuery::default())
+            .await
+    }
+
+    /// Same as [Self::list_tasks], yielding the tasks page by page as they arrive
+    /// instead of waiting for the last one, e.g. to stop early with
+    /// [futures::StreamExt::take]. The stream ends after the first error.
+    pub fn stream_tasks<'a>(
+        &'a self,
+        list_id: &str,
+    ) -> impl Stream<Item = Result<TodoTask, Error>> + 'a {
+        self.stream_tasks_with_query(list_id, &TaskQuery::default())
+    }
+
+    /// Same as [Self::list_tasks_with_query], yielding the tasks as they arrive
+    /// like [Self::stream_tasks]
+    pub fn stream_tasks_with_query<'a>(
+        &'a self,
+        list_id: &str,
+        query: &TaskQuery,
+    ) -> impl Stream<Item = Result<TodoTask, Error>> + 'a {
+        self.stream_all(
+            &format!("/me/todo/lists/{}/tasks", list_id),
+            &query.to_query_pairs(),
+        )
+    }
+
+    /// Fetches the tasks of every task list, keyed by list id.
+    /// Lists are fetched concurrently, up to the concurrency limit,
+    /// and a list failing does not prevent the others from being fetched.
+    pub async fn list_all_tasks(
+        &self,
+    ) -> Result<HashMap<String, Result<Vec<TodoTask>, Error>>, Error> {
+        let lists = self.list_task_lists().await?;
+        Ok(futures::stream::iter(lists)
+            .map(|list| async move {
+                let tasks = self.list_tasks(&list.id).await;
+                (list.id, tasks)
+            })
+            .buffer_unordered(self.concurrency_limit)
+            .collect()
+            .await)
+    }
+
+    /// Tasks of every list whose title or body contains `query`, ignoring case,
+    /// each paired with its list. Graph having no search across lists,
+    /// every task is downloaded and filtered locally.
+    /// Lists whose tasks cannot be read, e.g. no longer shared, are skipped with a warning.
+    pub async fn search_tasks(&self, query: &str) -> Result<Vec<(TodoTaskList, TodoTask)>, Error> {
+        let query = query.to_lowercase();
+        let matches = |text: &str| text.to_lowercase().contains(&query);
+        let lists = self.list_task_lists().await?;
+        let tasks: Vec<(TodoTaskList, Result<Vec<TodoTask>, Error>)> = futures::stream::iter(lists)
+            .map(|list| async move {
+                let tasks = self.list_tasks(&list.id).await;
+                (list, tasks)
+            })
+            .buffered(self.concurrency_limit)
+            .collect()
+            .await;
+        let mut hits = Vec::new();
+        for (list, tasks) in tasks {
+            let tasks = match tasks {
+                Ok(tasks) => tasks,
+                Err(err) => {
+                    tracing::warn!("skipping list {}: {}", list.display_name, err);
+                    continue;
+                }
+            };
+            for task in tasks {
+                let body_matches = task
+                    .body
+                    .as_ref()
+                    .is_some_and(|body| matches(&body.content));
+                if matches(&task.title) || body_matches {
+                    hits.push((list.clone(), task));
+                }
+            }
+        }
+        Ok(hits)
+    }
+
+    /// Tasks of a list as last cached, returned right away, along with a task
+    /// fetching them in the background to bring `cache` up to date.
+    /// That task resolves to the fetched tasks, or to the cached ones
+    /// when the server cannot be reached, see [Error::is_network].
+    #[cfg(feature = "sqlite")]
+    pub async fn list_tasks_cached(
+        self: &std::sync::Arc<Self>,
+        list_id: &str,
+        cache: &std::sync::Arc<std::sync::Mutex<crate::cache::Cache>>,
+    ) -> Result<
+        (
+            Vec<TodoTask>,
+            tokio::task::JoinHandle<Result<Vec<TodoTask>, Error>>,
+        ),
+        Error,
+    > {
+        fn lock(
+            cache: &std::sync::Mutex<crate::cache::Cache>,
+        ) -> std::sync::MutexGuard<'_, crate::cache::Cache> {
+            cache
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+        }
+
+        let cached = lock(cache).get_tasks(list_id)?;
+        let client = std::sync::Arc::clone(self);
+        let cache = std::sync::Arc::clone(cache);
+        let list_id = list_id.to_owned();
+        let refresh = tokio::spawn(async move {
+            match client.list_tasks(&list_id).await {
+                Ok(tasks) => {
+                    lock(&cache).replace_tasks(&list_id, &tasks)?;
+                    Ok(tasks)
+                }
+                Err(err) if err.is_network() => {
+                    tracing::warn!("using cached tasks: {}", err);
+                    lock(&cache).get_tasks(&list_id)
+                }
+                Err(err) => Err(err),
+            }
+        });
+        Ok((cached, refresh))
+    }
+
+    /// Number of tasks in a list, optionally only those matching an OData `filter`,
+    /// without downloading them
+    pub async fn count_tasks(&self, list_id: &str, filter: Option<&str>) -> Result<u64, Error> {
+        let mut req = self
+            .request(Method::GET, &format!("/me/todo/lists/{}/tasks", list_id))
+            .query(&[("$count", "true"), ("$top", "0")])
+            // counting is only supported with eventual consistency
+            .header("ConsistencyLevel", "eventual");
+        if let Some(filter) = filter {
+            req = req.query(&[("$filter", filter)]);
+        }
+        let resp = self.send(req).await?;
+        let page: GraphCollection<serde_json::Value> = parse_response(resp).await?;
+        page.count
+            .ok_or_else(|| Error::UnexpectedResponse("response has no @odata.count".to_owned()))
+    }
+
+    /// Number of tasks in a list that are not completed
+    pub async fn count_incomplete_tasks(&self, list_id: &str) -> Result<u64, Error> {
+        self.count_tasks(list_id, Some("status ne 'completed'"))
+            .await
+    }
+
+    /// Fetches the tasks of a task list matching `query`
+    pub async fn list_tasks_with_query(
+        &self,
+        list_id: &str,
+        query: &TaskQuery,
+    ) -> Result<Vec<TodoTask>, Error> {
+        self.get_all_with_query(
+            &format!("/me/todo/lists/{}/tasks", list_id),
+            &query.to_query_pairs(),
+        )
+        .await
+    }
+
+    /// Fetches the tasks changed since the query that returned `delta_link`,
+    /// or every task along with a first delta link when there is none
+    pub async fn list_tasks_delta(
+        &self,
+        list_id: &str,
+        delta_link: Option<&str>,
+    ) -> Result<DeltaPage, Error> {
+        let mut resp = match delta_link {
+            Some(link) => self.send(self.request_url(Method::GET, link)).await?,
+            None => {
+                let path = format!("/me/todo/lists/{}/tasks/delta", list_id);
+                self.send(self.request(Method::GET, &path)).await?
+            }
+        };
+        let mut page = DeltaPage::default();
+        loop {
+            let items: GraphCollection<DeltaItem> = parse_response(resp).await?;
+            for item in items.value {
+                match item {
+                    DeltaItem::Removed { id, .. } => page.removed.push(id),
+                    DeltaItem::Task(task) => page.tasks.push(*task),
+                }
+            }
+            match items.next_link {
+                Some(link) => resp = self.send(self.request_url(Method::GET, &link)).await?,
+                None => {
+                    page.delta_link = items.delta_link;
+                    break Ok(page);
+                }
+            }
+        }
+    }
+
+    /// Writes the tasks of a list to `writer` as an iCalendar file of VTODO components
+    pub async fn export_ics(
+        &self,
+        list_id: &str,
+        writer: impl std::io::Write,
+    ) -> Result<(), Error> {
+        let tasks = self.list_tasks(list_id).await?;
+        ics::write_calendar(&tasks, writer)
+    }
+
+    /// Fetches a single task
+    pub async fn get_task(&self, list_id: &str, task_id: &str) -> Result<TodoTask, Error> {
+        let req = self.request(
+            Method::GET,
+            &format!("/me/todo/lists/{}/tasks/{}", list_id, task_id),
+        );
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    /// Creates a task, the returned task carries its server-assigned id
+    pub async fn create_task(&self, list_id: &str, new_task: NewTask) -> Result<TodoTask, Error> {
+        let req = self
+            .request(Method::POST, &format!("/me/todo/lists/{}/tasks", list_id))
+            .json(&new_task.couple_reminder());
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    /// Creates every task of `new_tasks` in turn, carrying on after failures.
+    /// Results are in the same order as `new_tasks`.
+    pub async fn import_tasks(
+        &self,
+        list_id: &str,
+        new_tasks: Vec<NewTask>,
+    ) -> Vec<Result<TodoTask, Error>> {
+        let mut results = Vec::with_capacity(new_tasks.len());
+        for new_task in new_tasks {
+            results.push(self.create_task(list_id, new_task).await);
+        }
+        results
+    }
+
+    /// Updates the fields of a task that are set in `patch`.
+    /// With `if_match` set to the [TodoTask::etag] it was read with,
+    /// fails with [Error::PreconditionFailed] if it changed since.
+    pub async fn update_task(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        patch: TaskPatch,
+        if_match: Option<&str>,
+    ) -> Result<TodoTask, Error> {
+        let req = self
+            .request(
+                Method::PATCH,
+                &format!("/me/todo/lists/{}/tasks/{}", list_id, task_id),
+            )
+            .json(&patch.couple_reminder());
+        let resp = self.send(with_if_match(req, if_match)).await?;
+        parse_response(resp).await
+    }
+
+    pub async fn delete_task(&self, list_id: &str, task_id: &str) -> Result<(), Error> {
+        let req = self.request(
+            Method::DELETE,
+            &format!("/me/todo/lists/{}/tasks/{}", list_id, task_id),
+        );
+        let resp = self.send(req).await?;
+        check_response(resp).await?;
+        Ok(())
+    }
+
+    /// Moves a task to another list, along with its checklist.
+    /// To Do has no such operation, so the task is recreated in `dst_list_id`
+    /// and the original deleted once that succeeded, the moved task having a new id.
+    /// When copying the status or checklist fails, the copy is deleted again.
+    /// When either deletion fails, [Error::MoveIncomplete] carries the new task.
+    pub async fn move_task(
+        &self,
+        src_list_id: &str,
+        task_id: &str,
+        dst_list_id: &str,
+    ) -> Result<TodoTask, Error> {
+        let task = self.get_task(src_list_id, task_id).await?;
+        let items = self.list_checklist_items(src_list_id, task_id).await?;
+        let moved = self.create_task(dst_list_id, NewTask::from(&task)).await?;
+        let moved = match self
+            .copy_progress(dst_list_id, moved.clone(), &task, &items)
+            .await
+        {
+            Ok(moved) => moved,
+            Err(err) => {
+                return match self.delete_task(dst_list_id, &moved.id).await {
+                    Ok(()) => Err(err),
+                    Err(_) => Err(Error::MoveIncomplete {
+                        task: Box::new(moved),
+                        source: Box::new(err),
+                    }),
+                };
+            }
+        };
+        if let Err(err) = self.delete_task(src_list_id, task_id).await {
+            return Err(Error::MoveIncomplete {
+                task: Box::new(moved),
+                source: Box::new(err),
+            });
+        }
+        Ok(moved)
+    }
+
+    /// Copies the status and checklist of `task` to `moved`, its copy in `list_id`
+    async fn copy_progress(
+        &self,
+        list_id: &str,
+        mut moved: TodoTask,
+        task: &TodoTask,
+        items: &[ChecklistItem],
+    ) -> Result<TodoTask, Error> {
+        if task.status != TaskStatus::NotStarted {
+            moved = self
+                .set_task_status(
+                    list_id,
+                    &moved.id,
+                    requests::TaskStatusRequest {
+                        status: task.status.clone(),
+                        completed_date_time: task.completed_date_time.clone(),
+                    },
+                    None,
+                )
+                .await?;
+        }
+        for item in items {
+            let req = self
+                .request(Method::POST, &checklist_path(list_id, &moved.id))
+                .json(&requests::ChecklistItemRequest {
+                    display_name: Some(&item.display_name),
+                    is_checked: Some(item.is_checked),
+                });
+            let resp = self.send(req).await?;
+            check_response(resp).await?;
+        }
+        Ok(moved)
+    }
+
+    /// Marks a task as completed now, `if_match` being used as in [Self::update_task]
+    pub async fn complete_task(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        if_match: Option<&str>,
+    ) -> Result<TodoTask, Error> {
+        let completed_date_time =
+            DateTimeTimeZone::new(&crate::utils::format_utc(SystemTime::now()), models::UTC);
+        self.set_task_status(
+            list_id,
+            task_id,
+            requests::TaskStatusRequest {
+                status: TaskStatus::Completed,
+                completed_date_time: Some(completed_date_time),
+            },
+            if_match,
+        )
+        .await
+    }
+
+    /// Completes every task of `task_ids`, at most [Self::with_concurrency_limit] at a time,
+    /// carrying on after failures. Completing the failed ids again resumes the operation.
+    pub async fn complete_tasks(
+        &self,
+        list_id: &str,
+        task_ids: &[&str],
+    ) -> Result<BulkResult, Error> {
+        let results: Vec<(String, Result<TodoTask, Error>)> = futures::stream::iter(task_ids)
+            .map(|task_id| async move {
+                let result = self.complete_task(list_id, task_id, None).await;
+                (task_id.to_string(), result)
+            })
+            .buffered(self.concurrency_limit)
+            .collect()
+            .await;
+        let mut bulk = BulkResult::default();
+        for (task_id, result) in results {
+            match result {
+                Ok(_) => bulk.succeeded.push(task_id),
+                Err(err) => bulk.failed.push((task_id, err)),
+            }
+        }
+        Ok(bulk)
+    }
+
+    /// Marks a task as not started, clearing its completion date
+    pub async fn reopen_task(&self, list_id: &str, task_id: &str) -> Result<TodoTask, Error> {
+        self.set_task_status(
+            list_id,
+            task_id,
+            requests::TaskStatusRequest {
+                status: TaskStatus::NotStarted,
+                completed_date_time: None,
+            },
+            None,
+        )
+        .await
+    }
+
+    async fn set_task_status(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        body: requests::TaskStatusRequest,
+        if_match: Option<&str>,
+    ) -> Result<TodoTask, Error> {
+        let req = self
+            .request(
+                Method::PATCH,
+                &format!("/me/todo/lists/{}/tasks/{}", list_id, task_id),
+            )
+            .json(&body);
+        let resp = self.send(with_if_match(req, if_match)).await?;
+        parse_response(resp).await
+    }
+
+    pub async fn list_checklist_items(
+        &self,
+        list_id: &str,
+        task_id: &str,
+    ) -> Result<Vec<ChecklistItem>, Error> {
+        self.get_all(&checklist_path(list_id, task_id)).await
+    }
+
+    pub async fn create_checklist_item(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        display_name: &str,
+    ) -> Result<ChecklistItem, Error> {
+        let req = self
+            .request(Method::POST, &checklist_path(list_id, task_id))
+            .json(&requests::ChecklistItemRequest {
+                display_name: Some(display_name),
+                is_checked: None,
+            });
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    /// Checks or unchecks a checklist item
+    pub async fn check_item(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        item_id: &str,
+        checked: bool,
+    ) -> Result<ChecklistItem, Error> {
+        let req = self
+            .request(
+                Method::PATCH,
+                &format!("{}/{}", checklist_path(list_id, task_id), item_id),
+            )
+            .json(&requests::ChecklistItemRequest {
+                display_name: None,
+                is_checked: Some(checked),
+            });
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    pub async fn delete_checklist_item(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        item_id: &str,
+    ) -> Result<(), Error> {
+        let req = self.request(
+            Method::DELETE,
+            &format!("{}/{}", checklist_path(list_id, task_id), item_id),
+        );
+        let resp = self.send(req).await?;
+        check_response(resp).await?;
+        Ok(())
+    }
+
+    pub async fn list_linked_resources(
+        &self,
+        list_id: &str,
+        task_id: &str,
+    ) -> Result<Vec<LinkedResource>, Error> {
+        self.get_all(&linked_resources_path(list_id, task_id)).await
+    }
+
+    pub async fn create_linked_resource(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        resource: NewLinkedResource,
+    ) -> Result<LinkedResource, Error> {
+        let req = self
+            .request(Method::POST, &linked_resources_path(list_id, task_id))
+            .json(&resource);
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    pub async fn list_attachments(
+        &self,
+        list_id: &str,
+        task_id: &str,
+    ) -> Result<Vec<TaskFileAttachment>, Error> {
+        self.get_all(&attachments_path(list_id, task_id)).await
+    }
+
+    /// Attaches a file of at most 3 MB to a task, e.g. `("notes.txt", "text/plain", bytes)`
+    pub async fn add_attachment(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        name: &str,
+        content_type: &str,
+        bytes: &[u8],
+    ) -> Result<TaskFileAttachment, Error> {
+        use base64::Engine;
+        if bytes.len() > MAX_INLINE_ATTACHMENT_SIZE {
+            return Err(Error::InvalidInput(format!(
+                "attachments are limited to {} bytes, got {}",
+                MAX_INLINE_ATTACHMENT_SIZE,
+                bytes.len()
+            )));
+        }
+        let req = self
+            .request(Method::POST, &attachments_path(list_id, task_id))
+            .json(&requests::AttachmentRequest {
+                odata_type: "#microsoft.graph.taskFileAttachment",
+                name,
+                content_type,
+                content_bytes: base64::engine::general_purpose::STANDARD.encode(bytes),
+            });
+        let resp = self.send(req).await?;
+        parse_response(resp).await
+    }
+
+    /// Fetches the content of an attachment
+    pub async fn download_attachment(
+        &self,
+        list_id: &str,
+        task_id: &str,
+        attachment_id: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let req = self.request(
+            Method::GET,
+            &format!(
+                "{}/{}/$value",
+                attachments_path(list_id, task_id),
+                attachment_id
+            ),
+        );
+        let resp = check_response(self.send(req).await?).await?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+/// Matches ids before display names, so a list named after another's id cannot shadow it
+fn find_list(lists: Vec<TodoTaskList>, name_or_id: &str) -> Option<TodoTaskList> {
+    match lists.iter().position(|list| list.id == name_or_id) {
+        Some(i) => lists.into_iter().nth(i),
+        None => lists
+            .into_iter()
+            .find(|list| list.display_name == name_or_id),
+    }
+}
+
+fn attachments_path(list_id: &str, task_id: &str) -> String {
+    format!("/me/todo/lists/{}/tasks/{}/attachments", list_id, task_id)
+}
+
+fn checklist_path(list_id: &str, task_id: &str) -> String {
+    format!(
+        "/me/todo/lists/{}/tasks/{}/checklistItems",
+        list_id, task_id
+    )
+}
+
+fn linked_resources_path(list_id: &str, task_id: &str) -> String {
+    format!(
+        "/me/todo/lists/{}/tasks/{}/linkedResources",
+        list_id, task_id
+    )
+}
+
+/// Turns unsuccessful responses into errors,
+/// using the Graph error in the body when there is one
+async fn check_response(resp: reqwest::Response) -> Result<reqwest::Response, Error> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let body = resp.text().await?;
+    Err(status_error(status, body))
+}
+
+fn with_if_match(req: reqwest::RequestBuilder, etag: Option<&str>) -> reqwest::RequestBuilder {
+    match etag {
+        Some(etag) => req.header(reqwest::header::IF_MATCH, etag),
+        None => req,
+    }
+}
+
+/// Method, url and body of a request, e.g. `DELETE https://...`
+fn describe(req: &reqwest::Request) -> String {
+    let body = req
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(String::from_utf8_lossy);
+    match body {
+        Some(body) => format!("{} {} {}", req.method(), req.url(), body),
+        None => format!("{} {}", req.method(), req.url()),
+    }
+}
+
+/// Error for a response that was not successful
+pub(crate) fn status_error(status: reqwest::StatusCode, body: String) -> Error {
+    use reqwest::StatusCode;
+
+    let api_error = match ApiError::from_body(status.as_u16(), &body) {
+        Some(api_error) => api_error,
+        None if matches!(
+            status,
+            StatusCode::NOT_FOUND | StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED
+        ) =>
+        {
+            ApiError::unparsed(status, body)
+        }
+        None => return Error::UnexpectedResponse(body),
+    };
+    match status {
+        StatusCode::NOT_FOUND => Error::NotFound(api_error),
+        StatusCode::CONFLICT => Error::Conflict(api_error),
+        StatusCode::PRECONDITION_FAILED => Error::PreconditionFailed(api_error),
+        _ => Error::Api(api_error),
+    }
+}
+
+/// Deserializes the body of a successful response
+async fn parse_response<T: DeserializeOwned>(resp: reqwest::Response) -> Result<T, Error> {
+    Ok(check_response(resp).await?.json().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, body_partial_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn requests_carry_the_access_token() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .and(header("authorization", "Bearer access"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let resp = client
+            .request(reqwest::Method::GET, "/me/todo/lists")
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    #[tokio::test]
+    async fn list_task_lists_follows_next_links() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .and(wiremock::matchers::query_param("$skiptoken", "page2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::list_json("3", "Work")],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::list_json("1", "Tasks"), testing::list_json("2", "Home")],
+                "@odata.nextLink": format!("{}/v1.0/me/todo/lists?$skiptoken=page2", server.uri()),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let lists = client.list_task_lists().await.unwrap();
+        let ids: Vec<&str> = lists.iter().map(|list| list.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn create_task_list_posts_the_name() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path("/v1.0/me/todo/lists"))
+            .and(body_json(json!({ "displayName": "Groceries" })))
+            .respond_with(
+                ResponseTemplate::new(201).set_body_json(testing::list_json("l1", "Groceries")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let list = client.create_task_list("Groceries").await.unwrap();
+        assert_eq!(list.id, "l1");
+        assert_eq!(list.display_name, "Groceries");
+    }
+
+    #[tokio::test]
+    async fn create_task_list_rejects_empty_and_duplicate_names() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(409))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        assert!(matches!(
+            client.create_task_list("  ").await,
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            client.create_task_list("Groceries").await,
+            Err(Error::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn update_task_list_renames_the_list() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("PATCH"))
+            .and(path("/v1.0/me/todo/lists/l1"))
+            .and(body_json(json!({ "displayName": "Chores" })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::list_json("l1", "Chores")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let list = client.update_task_list("l1", "Chores").await.unwrap();
+        assert_eq!(list.display_name, "Chores");
+    }
+
+    #[tokio::test]
+    async fn delete_task_list_reports_missing_lists() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/l1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/gone"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        client.delete_task_list("l1").await.unwrap();
+        assert!(matches!(
+            client.delete_task_list("gone").await,
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn create_task_posts_the_new_task() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut created = testing::task_json("t1", "Buy milk");
+        created["importance"] = "high".into();
+        Mock::given(method("POST"))
+            .and(path("/v1.0/me/todo/lists/list/tasks"))
+            .and(body_json(
+                json!({ "title": "Buy milk", "importance": "high" }),
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_json(created))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let task = client
+            .create_task(
+                "list",
+                NewTask::new("Buy milk").importance(Importance::High),
+            )
+            .await
+            .unwrap();
+        assert_eq!(task.id, "t1");
+        assert_eq!(task.importance, Importance::High);
+    }
+
+    #[tokio::test]
+    async fn complete_task_sends_the_completion_date() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("PATCH"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1"))
+            .and(body_partial_json(json!({
+                "status": "completed",
+                "completedDateTime": { "timeZone": "UTC" },
+            })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::task_json("t1", "Buy milk")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        client.complete_task("list", "t1", None).await.unwrap();
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = requests[0].body_json().unwrap();
+        let completed = body["completedDateTime"]["dateTime"].as_str().unwrap();
+        assert!(!completed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reopen_task_clears_the_completion_date() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("PATCH"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1"))
+            .and(body_json(json!({
+                "status": "notStarted",
+                "completedDateTime": null,
+            })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::task_json("t1", "Buy milk")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        client.reopen_task("list", "t1").await.unwrap();
+    }
+
+    /// Graph error body of a missing task
+    fn not_found_json() -> serde_json::Value {
+        json!({
+            "error": {
+                "code": "ErrorItemNotFound",
+                "message": "The specified object was not found in the store.",
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn update_task_patches_the_task() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("PATCH"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1"))
+            .and(body_json(json!({ "title": "Buy oat milk" })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::task_json("t1", "Buy oat milk")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let patch = TaskPatch {
+            title: Some("Buy oat milk".to_owned()),
+            ..TaskPatch::default()
+        };
+        let task = client.update_task("list", "t1", patch, None).await.unwrap();
+        assert_eq!(task.title, "Buy oat milk");
+    }
+
+    #[tokio::test]
+    async fn delete_task_sends_a_delete() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        client.delete_task("list", "t1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_task_is_not_found() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(wiremock::matchers::path_regex("/tasks/gone$"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(not_found_json()))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let err = client
+            .update_task("list", "gone", TaskPatch::default(), None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::NotFound(e) if e.message.contains("not found")),
+            "{:?}",
+            err
+        );
+        let err = client.delete_task("list", "gone").await.unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn checklist_items_are_listed_created_and_checked() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let item = json!({
+            "id": "i1",
+            "displayName": "Transfer",
+            "isChecked": false,
+            "createdDateTime": "2024-05-01T00:00:00Z",
+        });
+        let items_path = "/v1.0/me/todo/lists/list/tasks/t1/checklistItems";
+        Mock::given(method("GET"))
+            .and(path(items_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "value": [item] })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(items_path))
+            .and(body_json(json!({ "displayName": "Transfer" })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(&item))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut checked = item.clone();
+        checked["isChecked"] = true.into();
+        Mock::given(method("PATCH"))
+            .and(path(format!("{}/i1", items_path)))
+            .and(body_json(json!({ "isChecked": true })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(checked))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let items = client.list_checklist_items("list", "t1").await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, "i1");
+        assert_eq!(items[0].display_name, "Transfer");
+        assert!(!items[0].is_checked);
+        let created = client
+            .create_checklist_item("list", "t1", "Transfer")
+            .await
+            .unwrap();
+        assert_eq!(created, items[0]);
+        let checked = client.check_item("list", "t1", "i1", true).await.unwrap();
+        assert!(checked.is_checked);
+    }
+
+    #[tokio::test]
+    async fn delta_returns_every_task_then_the_changes() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let delta_path = "/v1.0/me/todo/lists/list/tasks/delta";
+        let link = |param: &str| format!("{}{}?{}", server.uri(), delta_path, param);
+        Mock::given(method("GET"))
+            .and(path(delta_path))
+            .and(wiremock::matchers::query_param("$deltatoken", "first"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    testing::task_json("t3", "Call mum"),
+                    { "id": "t1", "@removed": { "reason": "deleted" } },
+                ],
+                "@odata.deltaLink": link("$deltatoken=second"),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(delta_path))
+            .and(wiremock::matchers::query_param("$skiptoken", "page"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::task_json("t2", "Buy milk")],
+                "@odata.deltaLink": link("$deltatoken=first"),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(delta_path))
+            .and(wiremock::matchers::query_param_is_missing("$skiptoken"))
+            .and(wiremock::matchers::query_param_is_missing("$deltatoken"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::task_json("t1", "Pay rent")],
+                "@odata.nextLink": link("$skiptoken=page"),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let initial = client.list_tasks_delta("list", None).await.unwrap();
+        let ids: Vec<&str> = initial.tasks.iter().map(|task| task.id.as_str()).collect();
+        assert_eq!(ids, vec!["t1", "t2"]);
+        assert!(initial.removed.is_empty());
+        let delta_link = initial.delta_link.unwrap();
+        assert_eq!(delta_link, link("$deltatoken=first"));
+
+        let changes = client
+            .list_tasks_delta("list", Some(&delta_link))
+            .await
+            .unwrap();
+        assert_eq!(changes.tasks.len(), 1);
+        assert_eq!(changes.tasks[0].title, "Call mum");
+        assert_eq!(changes.removed, vec!["t1".to_owned()]);
+        assert_eq!(changes.delta_link, Some(link("$deltatoken=second")));
+    }
+
+    #[tokio::test]
+    async fn tasks_and_master_categories_are_parsed() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut tagged = testing::task_json("t1", "Pay rent");
+        tagged["categories"] = json!(["Bills", "Home"]);
+        let untagged = testing::task_json("t2", "Buy milk");
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/list/tasks"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "value": [tagged, untagged] })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/outlook/masterCategories"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    { "id": "c1", "displayName": "Bills", "color": "preset0" },
+                    { "id": "c2", "displayName": "Home", "color": "none" },
+                ],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let tasks = client.list_tasks("list").await.unwrap();
+        assert_eq!(tasks[0].categories, vec!["Bills", "Home"]);
+        assert!(tasks[1].categories.is_empty());
+        let categories = client.list_categories().await.unwrap();
+        assert_eq!(
+            categories,
+            vec![
+                OutlookCategory {
+                    id: "c1".to_owned(),
+                    display_name: "Bills".to_owned(),
+                    color: "preset0".to_owned(),
+                },
+                OutlookCategory {
+                    id: "c2".to_owned(),
+                    display_name: "Home".to_owned(),
+                    color: "none".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttled_request_is_retried_after_the_wait() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::list_json("l1", "Tasks")],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let start = tokio::time::Instant::now();
+        let lists = client.list_task_lists().await.unwrap();
+        assert_eq!(lists[0].id, "l1");
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn rejected_token_is_refreshed_and_the_request_replayed() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let lists_path = "/v1.0/me/todo/lists";
+        Mock::given(method("GET"))
+            .and(path(lists_path))
+            .and(wiremock::matchers::header("Authorization", "Bearer access"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(lists_path))
+            .and(wiremock::matchers::header("Authorization", "Bearer fresh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::list_json("l1", "Tasks")],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::token_json("fresh", "rotated")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let lists = client.list_task_lists().await.unwrap();
+        assert_eq!(lists[0].id, "l1");
+    }
+
+    #[tokio::test]
+    async fn rejected_streamed_request_is_not_replayed() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "error": { "code": "InvalidAuthenticationToken", "message": "Token expired" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::token_json("fresh", "rotated")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let body = reqwest::Body::from(hyper::Body::from(r#"{"displayName":"Chores"}"#));
+        let req = client.request(Method::POST, "/me/todo/lists").body(body);
+        let err = client.send(req).await.unwrap_err();
+        match err {
+            Error::Api(api_error) => {
+                assert_eq!(api_error.status, 401);
+                assert_eq!(api_error.code, "InvalidAuthenticationToken");
+            }
+            err => panic!("{:?}", err),
+        }
+        assert_eq!(client.tokens.current().access_token, "fresh");
+    }
+
+    #[tokio::test]
+    async fn persistently_rejected_token_is_refreshed_once() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::token_json("fresh", "rotated")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let err = client.list_task_lists().await.unwrap_err();
+        assert!(
+            matches!(err, Error::Auth(AuthenticationError::AuthenticationFailed)),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_list_by_id_name_or_default() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut tasks = testing::list_json("l1", "Tasks");
+        tasks["wellknownListName"] = "defaultList".into();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    testing::list_json("l0", "Flagged emails"),
+                    tasks,
+                    testing::list_json("l2", "Groceries"),
+                ],
+            })))
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        assert_eq!(client.resolve_list("l2").await.unwrap().id, "l2");
+        assert_eq!(client.resolve_list("Groceries").await.unwrap().id, "l2");
+        let default = client.resolve_list("default").await.unwrap();
+        assert_eq!(default.id, "l1");
+        assert!(default.well_known_list_name.is_default());
+        let err = client.resolve_list("Chores").await.unwrap_err();
+        assert!(matches!(err, Error::NoMatch(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn missing_default_list_is_no_match() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::list_json("l2", "Groceries")],
+            })))
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let err = client.resolve_list("default").await.unwrap_err();
+        assert!(matches!(err, Error::NoMatch(_)), "{:?}", err);
+    }
+
+    /// Answers after `delay`, recording when each request arrived
+    struct ArrivalRecorder {
+        arrivals: std::sync::Arc<std::sync::Mutex<Vec<std::time::Instant>>>,
+        delay: Duration,
+    }
+
+    impl wiremock::Respond for ArrivalRecorder {
+        fn respond(&self, _request: &wiremock::Request) -> ResponseTemplate {
+            self.arrivals
+                .lock()
+                .unwrap()
+                .push(std::time::Instant::now());
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "value": [testing::task_json("t1", "Pay rent")] }))
+                .set_delay(self.delay)
+        }
+    }
+
+    #[tokio::test]
+    async fn list_all_tasks_fetches_two_lists_at_a_time() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    testing::list_json("l1", "Tasks"),
+                    testing::list_json("l2", "Groceries"),
+                    testing::list_json("l3", "Chores"),
+                ],
+            })))
+            .mount(&server)
+            .await;
+        let arrivals = std::sync::Arc::default();
+        let delay = Duration::from_millis(500);
+        Mock::given(method("GET"))
+            .and(wiremock::matchers::path_regex(
+                "^/v1.0/me/todo/lists/l[123]/tasks$",
+            ))
+            .respond_with(ArrivalRecorder {
+                arrivals: std::sync::Arc::clone(&arrivals),
+                delay,
+            })
+            .expect(3)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path()).with_concurrency_limit(2);
+
+        let tasks = client.list_all_tasks().await.unwrap();
+        let mut ids: Vec<&str> = tasks.keys().map(String::as_str).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["l1", "l2", "l3"]);
+        assert!(tasks
+            .values()
+            .all(|tasks| tasks.as_ref().unwrap().len() == 1));
+        let arrivals = arrivals.lock().unwrap();
+        // the first two lists are fetched together, the third once one of them is done
+        assert!(arrivals[1] - arrivals[0] < delay, "{:?}", arrivals);
+        assert!(arrivals[2] - arrivals[0] >= delay, "{:?}", arrivals);
+    }
+
+    /// Mocks the source task with a checklist item and its creation in `dst`
+    async fn mount_move(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/src/tasks/t1"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::task_json("t1", "Pay rent")),
+            )
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/src/tasks/t1/checklistItems"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [{
+                    "id": "i1",
+                    "displayName": "Transfer",
+                    "isChecked": true,
+                    "createdDateTime": "2024-05-01T00:00:00Z",
+                }],
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1.0/me/todo/lists/dst/tasks"))
+            .and(body_partial_json(json!({ "title": "Pay rent" })))
+            .respond_with(
+                ResponseTemplate::new(201).set_body_json(testing::task_json("t2", "Pay rent")),
+            )
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn move_task_copies_the_checklist_and_deletes_the_original() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_move(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/v1.0/me/todo/lists/dst/tasks/t2/checklistItems"))
+            .and(body_partial_json(
+                json!({ "displayName": "Transfer", "isChecked": true }),
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/src/tasks/t1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let moved = client.move_task("src", "t1", "dst").await.unwrap();
+        assert_eq!(moved.id, "t2");
+    }
+
+    #[tokio::test]
+    async fn move_task_reports_the_copy_when_the_original_is_not_deleted() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_move(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/v1.0/me/todo/lists/dst/tasks/t2/checklistItems"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({})))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/src/tasks/t1"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        match client.move_task("src", "t1", "dst").await {
+            Err(Error::MoveIncomplete { task, .. }) => assert_eq!(task.id, "t2"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn move_task_deletes_the_copy_when_the_checklist_is_not_copied() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        mount_move(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/v1.0/me/todo/lists/dst/tasks/t2/checklistItems"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/dst/tasks/t2"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/src/tasks/t1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let err = client.move_task("src", "t1", "dst").await.unwrap_err();
+        assert!(matches!(err, Error::UnexpectedResponse(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn create_subscription_posts_the_request() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path("/v1.0/subscriptions"))
+            .and(body_json(json!({
+                "changeType": "created,updated,deleted",
+                "notificationUrl": "https://example.com/notify",
+                "resource": "/me/todo/lists/list/tasks",
+                "expirationDateTime": "2024-05-03T00:00:00Z",
+                "clientState": "secret",
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "@odata.context": "https://graph.microsoft.com/v1.0/$metadata#subscriptions/$entity",
+                "id": "s1",
+                "resource": "/me/todo/lists/list/tasks",
+                "applicationId": "app",
+                "changeType": "created,updated,deleted",
+                "clientState": "secret",
+                "notificationUrl": "https://example.com/notify",
+                "expirationDateTime": "2024-05-03T00:00:00Z",
+                "creatorId": "user",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let subscription = client
+            .create_subscription(
+                SubscriptionRequest::for_list(
+                    "list",
+                    "https://example.com/notify",
+                    "2024-05-03T00:00:00Z",
+                )
+                .client_state("secret"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(subscription.id, "s1");
+        assert_eq!(subscription.resource, "/me/todo/lists/list/tasks");
+        assert_eq!(subscription.change_type, "created,updated,deleted");
+        assert_eq!(subscription.client_state.as_deref(), Some("secret"));
+        assert_eq!(
+            serde_json::to_value(&subscription).unwrap()["expirationDateTime"],
+            "2024-05-03T00:00:00Z"
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_responses_are_put_back_in_order() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .and(path("/v1.0/$batch"))
+            .and(body_json(json!({
+                "requests": [
+                    { "id": "0", "method": "GET", "url": "/me/todo/lists/list/tasks/t1" },
+                    {
+                        "id": "1",
+                        "method": "PATCH",
+                        "url": "/me/todo/lists/list/tasks/t2",
+                        "body": { "title": "Buy oat milk" },
+                        "headers": { "Content-Type": "application/json" },
+                    },
+                ],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "responses": [
+                    { "id": "1", "status": 404, "body": not_found_json() },
+                    {
+                        "id": "0",
+                        "status": 200,
+                        "headers": { "Content-Type": "application/json" },
+                        "body": testing::task_json("t1", "Pay rent"),
+                    },
+                ],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let responses = client
+            .batch(vec![
+                BatchRequest::get("/me/todo/lists/list/tasks/t1"),
+                BatchRequest::patch(
+                    "/me/todo/lists/list/tasks/t2",
+                    &json!({ "title": "Buy oat milk" }),
+                )
+                .unwrap(),
+            ])
+            .await
+            .unwrap();
+        let ids: Vec<&str> = responses.iter().map(|resp| resp.id.as_str()).collect();
+        assert_eq!(ids, vec!["0", "1"]);
+        let mut responses = responses.into_iter();
+        let task: TodoTask = responses.next().unwrap().parse().unwrap();
+        assert_eq!(task.id, "t1");
+        let err = responses.next().unwrap().parse::<TodoTask>().unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn changes_without_the_write_scope_are_not_sent() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let mut auth = testing::token_json("access", "refresh");
+        auth["scope"] = "Tasks.Read User.Read".into();
+        let client =
+            testing::client_builder(&server)
+                .require_scopes(true)
+                .build(TokenProvider::new(
+                    serde_json::from_value(auth).unwrap(),
+                    testing::authenticator(&server, dir.path()),
+                ));
+
+        let err = client
+            .create_task("list", NewTask::new("Buy milk"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::MissingScope(scope) if scope == "Tasks.ReadWrite"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn stored_token_is_read_once() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1"))
+            .and(wiremock::matchers::header("Authorization", "Bearer stored"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::task_json("t1", "Pay rent")),
+            )
+            .expect(3)
+            .mount(&server)
+            .await;
+        let store = testing::token_store(dir.path());
+        store
+            .save(&testing::auth_response("stored", "refresh"))
+            .unwrap();
+        let tokens = TokenProvider::login(testing::authenticator(&server, dir.path()))
+            .await
+            .unwrap();
+        // any further read of the store would find nothing
+        store.clear().unwrap();
+        let client = testing::client_builder(&server).build(tokens);
+
+        for _ in 0..3 {
+            client.get_task("list", "t1").await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn import_in_dry_run_sends_nothing() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path()).with_dry_run(true);
+
+        let results = client
+            .import_tasks("list", vec![NewTask::new("a"), NewTask::new("b")])
+            .await;
+        assert_eq!(results.len(), 2);
+        for result in results {
+            match result {
+                Err(Error::DryRun(request)) => {
+                    assert!(request.starts_with("POST "), "{}", request);
+                    assert!(request.contains("/me/todo/lists/list/tasks"), "{}", request);
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn raw_get_is_relative_to_the_api_root() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let attachments = json!({ "value": [{ "id": "a1", "name": "receipt.pdf" }] });
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1/attachments"))
+            .and(wiremock::matchers::header("Authorization", "Bearer access"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&attachments))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1/attachments/a1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let value: serde_json::Value = client
+            .raw_get("/me/todo/lists/list/tasks/t1/attachments")
+            .await
+            .unwrap();
+        assert_eq!(value, attachments);
+        let value: serde_json::Value = client
+            .raw_request(
+                Method::DELETE,
+                "/me/todo/lists/list/tasks/t1/attachments/a1",
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(value, serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn inline_attachment_round_trip() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let attachments_path = "/v1.0/me/todo/lists/list/tasks/t1/attachments";
+        Mock::given(method("POST"))
+            .and(path(attachments_path))
+            .and(body_json(json!({
+                "@odata.type": "#microsoft.graph.taskFileAttachment",
+                "name": "notes.txt",
+                "contentType": "text/plain",
+                "contentBytes": "aGVsbG8gd29ybGQ=",
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "@odata.type": "#microsoft.graph.taskFileAttachment",
+                "id": "a1",
+                "name": "notes.txt",
+                "contentType": "text/plain",
+                "size": 11,
+                "lastModifiedDateTime": "2024-05-01T00:00:00Z",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{}/a1/$value", attachments_path)))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes("hello world"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let attachment = client
+            .add_attachment("list", "t1", "notes.txt", "text/plain", b"hello world")
+            .await
+            .unwrap();
+        assert_eq!(
+            attachment,
+            TaskFileAttachment {
+                id: "a1".to_owned(),
+                name: "notes.txt".to_owned(),
+                content_type: "text/plain".to_owned(),
+                size: 11,
+            }
+        );
+        let content = client
+            .download_attachment("list", "t1", &attachment.id)
+            .await
+            .unwrap();
+        assert_eq!(content, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn oversized_attachment_is_not_sent() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let client = testing::client(&server, dir.path());
+
+        let bytes = vec![0; MAX_INLINE_ATTACHMENT_SIZE + 1];
+        let err = client
+            .add_attachment("list", "t1", "big.bin", "application/octet-stream", &bytes)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(_)), "{:?}", err);
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn count_incomplete_tasks_reads_the_count() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/list/tasks"))
+            .and(wiremock::matchers::query_param("$count", "true"))
+            .and(wiremock::matchers::query_param("$top", "0"))
+            .and(wiremock::matchers::query_param(
+                "$filter",
+                "status ne 'completed'",
+            ))
+            .and(wiremock::matchers::header("ConsistencyLevel", "eventual"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "@odata.context": "https://graph.microsoft.com/v1.0/$metadata#me/todo/lists('list')/tasks",
+                "@odata.count": 42,
+                "value": [],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        assert_eq!(client.count_incomplete_tasks("list").await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn count_without_odata_count_is_unexpected() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/list/tasks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "value": [] })))
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let err = client.count_tasks("list", None).await.unwrap_err();
+        assert!(matches!(err, Error::UnexpectedResponse(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn update_with_a_stale_etag_is_rejected() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("PATCH"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1"))
+            .and(wiremock::matchers::header("If-Match", "W/\"current\""))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::task_json("t1", "Buy oat milk")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/t1"))
+            .respond_with(ResponseTemplate::new(412).set_body_json(json!({
+                "error": {
+                    "code": "ErrorIrresolvableConflict",
+                    "message": "The change key passed in the request does not match the current change key for the item.",
+                },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+        let patch = TaskPatch {
+            title: Some("Buy oat milk".to_owned()),
+            ..TaskPatch::default()
+        };
+
+        let err = client
+            .update_task("list", "t1", patch.clone(), Some("W/\"stale\""))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::PreconditionFailed(e) if e.code == "ErrorIrresolvableConflict"),
+            "{:?}",
+            err
+        );
+        let task = client
+            .update_task("list", "t1", patch, Some("W/\"current\""))
+            .await
+            .unwrap();
+        assert_eq!(task.title, "Buy oat milk");
+    }
+
+    #[test]
+    fn not_found_carries_the_graph_error() {
+        let body = json!({
+            "error": {
+                "code": "ErrorItemNotFound",
+                "message": "The specified object was not found in the store.",
+                "innerError": { "request-id": "request", "date": "2024-05-01T00:00:00" },
+            },
+        });
+        let err = status_error(reqwest::StatusCode::NOT_FOUND, body.to_string());
+        let Error::NotFound(api_error) = &err else {
+            panic!("unexpected {:?}", err);
+        };
+        assert_eq!(api_error.code, "ErrorItemNotFound");
+        assert_eq!(api_error.request_id.as_deref(), Some("request"));
+        assert_eq!(err.api_error(), Some(api_error));
+    }
+
+    #[test]
+    fn conflict_without_graph_error_keeps_the_body() {
+        let err = status_error(reqwest::StatusCode::CONFLICT, "busy".to_owned());
+        let Error::Conflict(api_error) = err else {
+            panic!("unexpected {:?}", err);
+        };
+        assert_eq!(api_error.status, 409);
+        assert_eq!(api_error.message, "busy");
+    }
+
+    #[test]
+    fn other_status_without_graph_error_is_unexpected() {
+        let err = status_error(reqwest::StatusCode::BAD_GATEWAY, "<html>".to_owned());
+        assert!(matches!(err, Error::UnexpectedResponse(body) if body == "<html>"));
+    }
+
+    #[tokio::test]
+    async fn complete_tasks_keeps_going_after_a_failure() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("PATCH"))
+            .and(path("/v1.0/me/todo/lists/list/tasks/gone"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(not_found_json()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(wiremock::matchers::path_regex("/tasks/t[12]$"))
+            .and(body_partial_json(json!({ "status": "completed" })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::task_json("t1", "Pay rent")),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let result = client
+            .complete_tasks("list", &["t1", "gone", "t2"])
+            .await
+            .unwrap();
+        assert_eq!(result.succeeded, vec!["t1", "t2"]);
+        assert_eq!(result.failed.len(), 1);
+        let (id, err) = &result.failed[0];
+        assert_eq!(id, "gone");
+        assert!(matches!(err, Error::NotFound(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn search_tasks_across_lists_skipping_unreadable_ones() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    testing::list_json("l1", "Groceries"),
+                    testing::list_json("l2", "Chores"),
+                    testing::list_json("l3", "Unshared"),
+                ],
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/l3/tasks"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "error": { "code": "accessDenied", "message": "Access is denied" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut noted = testing::task_json("t3", "Clean fridge");
+        noted["body"]["content"] = "throw out the old MILK".into();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/l1/tasks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    testing::task_json("t1", "Buy milk"),
+                    testing::task_json("t2", "Buy bread"),
+                ],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/l2/tasks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [noted, testing::task_json("t4", "Water plants")],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let hits = client.search_tasks("Milk").await.unwrap();
+        let hits: Vec<(&str, &str)> = hits
+            .iter()
+            .map(|(list, task)| (list.display_name.as_str(), task.id.as_str()))
+            .collect();
+        assert_eq!(hits, vec![("Groceries", "t1"), ("Chores", "t3")]);
+    }
+
+    #[tokio::test]
+    async fn stream_tasks_yields_both_pages_lazily() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let tasks_path = "/v1.0/me/todo/lists/list/tasks";
+        Mock::given(method("GET"))
+            .and(path(tasks_path))
+            .and(wiremock::matchers::query_param("$skiptoken", "page2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [testing::task_json("t3", "Call mum")],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(tasks_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    testing::task_json("t1", "Buy milk"),
+                    testing::task_json("t2", "Pay rent"),
+                ],
+                "@odata.nextLink": format!("{}{}?$skiptoken=page2", server.uri(), tasks_path),
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let first: Vec<TodoTask> = client
+            .stream_tasks("list")
+            .take(2)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(first.len(), 2);
+        // the second page is only fetched once the first one is used up
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let tasks: Vec<TodoTask> = client.stream_tasks("list").try_collect().await.unwrap();
+        let ids: Vec<&str> = tasks.iter().map(|task| task.id.as_str()).collect();
+        assert_eq!(ids, vec!["t1", "t2", "t3"]);
+    }
+
+    #[tokio::test]
+    async fn ping_measures_the_latency() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me"))
+            .and(wiremock::matchers::query_param("$select", "id"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "id": "user" }))
+                    .set_delay(Duration::from_millis(20)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let latency = client.ping().await.unwrap();
+        assert!(latency >= Duration::from_millis(20), "{:?}", latency);
+    }
+
+    #[tokio::test]
+    async fn ping_with_a_rejected_token_is_an_auth_error() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(testing::TOKEN_PATH))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(testing::oauth_error_json("invalid_grant")),
+            )
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let err = client.ping().await.unwrap_err();
+        assert!(matches!(err, Error::Auth(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn members_of_a_shared_and_a_private_list() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut shared = testing::list_json("shared", "Groceries");
+        shared["isShared"] = true.into();
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/shared"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(shared))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/shared/allowedAudience"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    {
+                        "id": "u1",
+                        "displayName": "Ada Lovelace",
+                        "userPrincipalName": "ada@example.com",
+                    },
+                    { "displayName": "Charles Babbage" },
+                ],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/private"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(testing::list_json("private", "Diary")),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1.0/me/todo/lists/private/allowedAudience"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server, dir.path());
+
+        let members = client.list_members("shared").await.unwrap();
+        assert_eq!(
+            members,
+            vec![
+                ListMember {
+                    id: Some("u1".to_owned()),
+                    display_name: Some("Ada Lovelace".to_owned()),
+                    user_principal_name: Some("ada@example.com".to_owned()),
+                },
+                ListMember {
+                    id: None,
+                    display_name: Some("Charles Babbage".to_owned()),
+                    user_principal_name: None,
+                },
+            ]
+        );
+        assert!(client.list_members("private").await.unwrap().is_empty());
+    }
+}
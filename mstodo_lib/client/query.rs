@@ -0,0 +1,143 @@
+//! OData query options narrowing down the tasks returned by the server
+//! See "https://learn.microsoft.com/en-us/graph/query-parameters"
+//! for more information
+
+/// Properties [crate::models::TodoTask] cannot be deserialized without,
+/// always added to `$select`
+const REQUIRED_PROPERTIES: &[&str] = &[
+    "id",
+    "title",
+    "status",
+    "importance",
+    "isReminderOn",
+    "createdDateTime",
+    "lastModifiedDateTime",
+];
+
+/// Query options for [super::TodoClient::list_tasks_with_query].
+/// Expressions are passed through as is, validating them is left to the server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskQuery {
+    filter: Option<String>,
+    order_by: Vec<String>,
+    select: Vec<String>,
+    top: Option<u32>,
+}
+
+impl TaskQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// e.g. `status eq 'completed'`
+    pub fn filter(mut self, filter: &str) -> Self {
+        self.filter = Some(filter.to_owned());
+        self
+    }
+
+    /// e.g. `dueDateTime/dateTime desc`, can be repeated to order by several properties
+    pub fn order_by(mut self, order_by: &str) -> Self {
+        self.order_by.push(order_by.to_owned());
+        self
+    }
+
+    /// Properties to return on top of the ones every task has,
+    /// e.g. `["dueDateTime"]`
+    pub fn select(mut self, properties: &[&str]) -> Self {
+        self.select
+            .extend(properties.iter().map(|property| property.to_string()));
+        self
+    }
+
+    /// Maximum number of tasks per page
+    pub fn top(mut self, top: u32) -> Self {
+        self.top = Some(top);
+        self
+    }
+
+    /// Query parameters to append to the request, values are encoded when sent
+    pub fn to_query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        if let Some(filter) = &self.filter {
+            pairs.push(("$filter", filter.clone()));
+        }
+        if !self.order_by.is_empty() {
+            pairs.push(("$orderby", self.order_by.join(",")));
+        }
+        if !self.select.is_empty() {
+            let mut select: Vec<&str> = REQUIRED_PROPERTIES.to_vec();
+            for property in &self.select {
+                if !select.contains(&property.as_str()) {
+                    select.push(property);
+                }
+            }
+            pairs.push(("$select", select.join(",")));
+        }
+        if let Some(top) = self.top {
+            pairs.push(("$top", top.to_string()));
+        }
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_keeps_the_required_properties() {
+        let pairs = TaskQuery::new()
+            .select(&["title", "dueDateTime"])
+            .to_query_pairs();
+        assert_eq!(
+            pairs,
+            vec![(
+                "$select",
+                "id,title,status,importance,isReminderOn,createdDateTime,\
+                 lastModifiedDateTime,dueDateTime"
+                    .to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn query_string() {
+        let query = TaskQuery::new()
+            .filter("status eq 'completed'")
+            .order_by("createdDateTime desc")
+            .top(10);
+        let url = reqwest::Url::parse_with_params(
+            "https://graph.microsoft.com/v1.0/me/todo/lists/1/tasks",
+            query.to_query_pairs(),
+        )
+        .unwrap();
+        assert_eq!(
+            url.query(),
+            Some(
+                "%24filter=status+eq+%27completed%27\
+                 &%24orderby=createdDateTime+desc&%24top=10"
+            )
+        );
+    }
+
+    #[test]
+    fn empty_query_has_no_pairs() {
+        assert!(TaskQuery::new().to_query_pairs().is_empty());
+    }
+
+    #[test]
+    fn task_with_only_the_required_properties_deserializes() {
+        let task: crate::client::models::TodoTask = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "title": "Buy milk",
+            "status": "notStarted",
+            "importance": "normal",
+            "isReminderOn": false,
+            "createdDateTime": "2024-05-01T00:00:00Z",
+            "lastModifiedDateTime": "2024-05-01T00:00:00Z",
+        }))
+        .unwrap();
+        assert_eq!(task.title, "Buy milk");
+        assert_eq!(task.reminder_date_time, None);
+    }
+}
@@ -0,0 +1,90 @@
+//! Request bodies sent to the To Do API
+use crate::models::{BatchRequest, DateTimeTimeZone, TaskStatus};
+
+/// Body of a task list creation or update
+/// See "https://learn.microsoft.com/en-us/graph/api/todo-post-lists"
+/// for more information
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct TaskListRequest<'req> {
+    pub display_name: &'req str,
+}
+
+/// Body of a task update changing its status.
+/// `completed_date_time` is always sent so reopening a task clears it.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct TaskStatusRequest {
+    pub status: TaskStatus,
+    pub completed_date_time: Option<DateTimeTimeZone>,
+}
+
+/// Body of a checklist item creation or update
+/// See "https://learn.microsoft.com/en-us/graph/api/todotask-post-checklistitems"
+/// for more information
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ChecklistItemRequest<'req> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<&'req str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_checked: Option<bool>,
+}
+
+/// Body of a subscription renewal
+/// See "https://learn.microsoft.com/en-us/graph/api/subscription-update"
+/// for more information
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SubscriptionRenewalRequest<'req> {
+    pub expiration_date_time: &'req str,
+}
+
+/// Body of a `$batch` request
+/// See "https://learn.microsoft.com/en-us/graph/json-batching"
+/// for more information
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub(super) struct BatchRequestBody<'req> {
+    pub requests: Vec<BatchItem<'req>>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub(super) struct BatchItem<'req> {
+    pub id: String,
+    pub method: &'req str,
+    pub url: &'req str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<&'req serde_json::Value>,
+    /// Graph requires the content type of sub-requests with a body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers: Option<serde_json::Value>,
+}
+
+impl<'req> BatchItem<'req> {
+    pub fn new(id: String, request: &'req BatchRequest) -> Self {
+        Self {
+            id,
+            method: request.method.as_str(),
+            url: &request.url,
+            body: request.body.as_ref(),
+            headers: request
+                .body
+                .as_ref()
+                .map(|_| serde_json::json!({ "Content-Type": "application/json" })),
+        }
+    }
+}
+
+/// Body of an inline file attachment upload
+/// See "https://learn.microsoft.com/en-us/graph/api/todotask-post-attachments"
+/// for more information
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AttachmentRequest<'req> {
+    #[serde(rename = "@odata.type")]
+    pub odata_type: &'static str,
+    pub name: &'req str,
+    pub content_type: &'req str,
+    /// Base64 encoded content
+    pub content_bytes: String,
+}
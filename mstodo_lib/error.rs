@@ -1,15 +1,424 @@
 use thiserror;
 
+/// Error returned by every public function of the crate
 #[derive(thiserror::Error, Debug)]
-#[error("{0}")]
-pub struct Error(Box<dyn std::error::Error>);
+pub enum Error {
+    #[error("Authentication error: {0}")]
+    Auth(#[from] AuthenticationError),
+    #[error("API error: {0}")]
+    Api(ApiError),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Serialization error: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("Token store error: {0}")]
+    Store(#[from] StoreError),
+    #[error("Date time error: {0}")]
+    DateTime(#[from] DateTimeError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("Cache error: {0}")]
+    Cache(#[from] rusqlite::Error),
+    #[error("Not found: {}", .0.message)]
+    NotFound(ApiError),
+    /// Nothing matched a lookup made by this crate,
+    /// e.g. a list name in [crate::client::TodoClient::resolve_list]
+    #[error("Not found: {0}")]
+    NoMatch(String),
+    #[error("Conflict: {}", .0.message)]
+    Conflict(ApiError),
+    /// The resource changed since the ETag sent in `If-Match` was read
+    #[error("Precondition failed: {}", .0.message)]
+    PreconditionFailed(ApiError),
+    #[error("Throttled, retry after {0:?}")]
+    Throttled(std::time::Duration),
+    #[error("Missing scope: {0}")]
+    MissingScope(String),
+    /// A change was not sent because the client is in dry-run mode,
+    /// the request being described instead
+    #[error("Dry run: {0}")]
+    DryRun(String),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    /// Some of the tasks acted on at once failed, each having been reported on its own
+    #[error("{failed} of {total} tasks failed")]
+    TasksFailed { failed: usize, total: usize },
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+    /// The task was copied by [crate::client::TodoClient::move_task]
+    /// but the move could not be completed nor undone, so it exists in both lists
+    #[error("Task copied as {} but the move could not be completed: {source}", task.id)]
+    MoveIncomplete {
+        task: Box<crate::models::TodoTask>,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// The error returned by Graph, with the ids to give Microsoft support
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Error::Api(err)
+            | Error::NotFound(err)
+            | Error::Conflict(err)
+            | Error::PreconditionFailed(err) => Some(err),
+            Error::MoveIncomplete { source, .. } => source.api_error(),
+            _ => None,
+        }
+    }
+
+    /// Whether the server could not be reached or did not answer in time,
+    /// as opposed to it answering with an error
+    pub fn is_network(&self) -> bool {
+        match self {
+            Error::Http(err) => {
+                err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+            }
+            Error::Auth(AuthenticationError::NetworkError { .. }) => true,
+            _ => false,
+        }
+    }
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthenticationError {
-    #[error("Network error: {0}")]
-    NetworkError(#[from] reqwest::Error),
+    #[error("Network error ({kind}): {source}")]
+    NetworkError {
+        kind: NetworkErrorKind,
+        source: reqwest::Error,
+    },
     #[error("Authentication failed")]
     AuthenticationFailed,
+    #[error("Authorization declined: {0}")]
+    AuthorizationDeclined(OAuthError),
+    #[error("Device code expired: {0}")]
+    DeviceCodeExpired(OAuthError),
+    #[error("Authorization failed: {0}")]
+    AuthorizationFailed(OAuthError),
+    #[error("Bad verification code: {0}")]
+    BadVerificationCode(OAuthError),
+    #[error("Invalid client: {0}")]
+    InvalidClient(OAuthError),
+    #[error("Invalid scope: {0}")]
+    InvalidScope(OAuthError),
     #[error("Unexpected response: {0}")]
     UnexpectedResponse(String),
+    #[error("Authentication cancelled")]
+    Cancelled,
+    #[error("Invalid tenant: {0}")]
+    InvalidTenant(String),
+    #[error("No stored token found")]
+    NoStoredToken,
+    /// The server did not return a refresh token, so the access token cannot be renewed
+    #[error("No refresh token")]
+    NoRefreshToken,
+    #[error("Token store error: {0}")]
+    TokenStoreError(#[from] StoreError),
+}
+
+impl From<reqwest::Error> for AuthenticationError {
+    fn from(source: reqwest::Error) -> Self {
+        Self::NetworkError {
+            kind: NetworkErrorKind::of(&source),
+            source,
+        }
+    }
+}
+
+/// What went wrong with a request that got no response,
+/// telling apart failures the user may fix on their side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkErrorKind {
+    /// The server did not answer in time
+    Timeout,
+    /// The server could not be reached, e.g. a DNS failure or a refused connection
+    Connect,
+    /// The TLS handshake failed, e.g. an untrusted certificate
+    Tls,
+    Other,
+}
+
+impl NetworkErrorKind {
+    pub fn of(err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return Self::Timeout;
+        }
+        // reqwest reports TLS failures as connection errors,
+        // only the messages of the underlying errors telling them apart
+        let mut source = std::error::Error::source(err);
+        while let Some(cause) = source {
+            let message = cause.to_string().to_ascii_lowercase();
+            if ["tls", "ssl", "certificate", "handshake"]
+                .iter()
+                .any(|word| message.contains(word))
+            {
+                return Self::Tls;
+            }
+            source = cause.source();
+        }
+        if err.is_connect() {
+            Self::Connect
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl std::fmt::Display for NetworkErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Timeout => "timed out",
+            Self::Connect => "could not connect",
+            Self::Tls => "TLS failure",
+            Self::Other => "request failed",
+        })
+    }
+}
+
+/// Error description returned by the identity platform, along with
+/// the ids Microsoft support asks for when investigating a failure
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OAuthError {
+    pub description: String,
+    pub trace_id: Option<String>,
+    pub correlation_id: Option<String>,
+}
+
+impl OAuthError {
+    /// Error raised locally, without server side ids
+    pub fn new(description: &str) -> Self {
+        Self {
+            description: description.to_owned(),
+            ..Self::default()
+        }
+    }
+}
+
+impl From<crate::auth::responses::DeviceCodeAuthenticationError> for OAuthError {
+    fn from(err: crate::auth::responses::DeviceCodeAuthenticationError) -> Self {
+        Self {
+            description: err.error_description,
+            trace_id: Some(err.trace_id),
+            correlation_id: Some(err.correlation_id),
+        }
+    }
+}
+
+impl std::fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+/// Error returned by Microsoft Graph
+/// See "https://learn.microsoft.com/en-us/graph/errors"
+/// for more information
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("{status} {code}: {message}")]
+pub struct ApiError {
+    /// HTTP status of the response
+    pub status: u16,
+    pub code: String,
+    pub message: String,
+    pub request_id: Option<String>,
+    pub date: Option<String>,
+}
+
+/// `{ "error": { "code", "message", "innerError": { "request-id", "date" } } }`
+#[derive(serde::Deserialize)]
+struct ApiErrorEnvelope {
+    error: ApiErrorBody,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiErrorBody {
+    code: String,
+    message: String,
+    inner_error: Option<InnerError>,
+}
+
+#[derive(serde::Deserialize)]
+struct InnerError {
+    #[serde(rename = "request-id")]
+    request_id: Option<String>,
+    date: Option<String>,
+}
+
+impl ApiError {
+    /// Parses the body of an unsuccessful response,
+    /// [None] when it is not a Graph error
+    pub fn from_body(status: u16, body: &str) -> Option<Self> {
+        let envelope: ApiErrorEnvelope = serde_json::from_str(body).ok()?;
+        let inner_error = envelope.error.inner_error;
+        Some(Self {
+            status,
+            code: envelope.error.code,
+            message: envelope.error.message,
+            request_id: inner_error.as_ref().and_then(|e| e.request_id.clone()),
+            date: inner_error.and_then(|e| e.date),
+        })
+    }
+
+    /// Error for a body that is not a Graph error, kept as the message
+    pub(crate) fn unparsed(status: reqwest::StatusCode, body: String) -> Self {
+        Self {
+            status: status.as_u16(),
+            code: status.canonical_reason().unwrap_or_default().to_owned(),
+            message: body,
+            request_id: None,
+            date: None,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StoreError {
+    #[error("Keyring error: {0}")]
+    KeyringError(#[from] keyring::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Unable to locate the config directory")]
+    NoConfigDir,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DateTimeError {
+    #[error("Unsupported time zone: {0}")]
+    UnsupportedTimeZone(String),
+    #[error("Invalid date time: {0}")]
+    InvalidDateTime(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_error_from_graph_body() {
+        let body = r#"{
+            "error": {
+                "code": "ErrorItemNotFound",
+                "message": "The specified object was not found in the store.",
+                "innerError": {
+                    "date": "2024-05-01T08:30:00",
+                    "request-id": "b0a6c7e2-0000-0000-0000-000000000000",
+                    "client-request-id": "b0a6c7e2-0000-0000-0000-000000000000"
+                }
+            }
+        }"#;
+        let err = ApiError::from_body(404, body).unwrap();
+        assert_eq!(
+            err,
+            ApiError {
+                status: 404,
+                code: "ErrorItemNotFound".to_owned(),
+                message: "The specified object was not found in the store.".to_owned(),
+                request_id: Some("b0a6c7e2-0000-0000-0000-000000000000".to_owned()),
+                date: Some("2024-05-01T08:30:00".to_owned()),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "404 ErrorItemNotFound: The specified object was not found in the store."
+        );
+    }
+
+    #[test]
+    fn api_error_from_malformed_body() {
+        assert_eq!(ApiError::from_body(502, "<html>Bad Gateway</html>"), None);
+        assert_eq!(ApiError::from_body(400, r#"{"error": "invalid"}"#), None);
+        assert_eq!(ApiError::from_body(400, ""), None);
+    }
+
+    #[test]
+    fn errors_convert_into_error() {
+        let err = Error::from(AuthenticationError::AuthenticationFailed);
+        assert!(matches!(
+            err,
+            Error::Auth(AuthenticationError::AuthenticationFailed)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Authentication error: Authentication failed"
+        );
+        let err = Error::from(serde_json::from_str::<u32>("x").unwrap_err());
+        assert!(matches!(err, Error::Serde(_)));
+    }
+
+    #[test]
+    fn oauth_error_keeps_the_support_ids() {
+        let err = OAuthError {
+            description: "AADSTS70000: declined".to_owned(),
+            trace_id: Some("trace".to_owned()),
+            correlation_id: Some("correlation".to_owned()),
+        };
+        assert_eq!(err.to_string(), "AADSTS70000: declined");
+        let err = Error::from(AuthenticationError::AuthorizationDeclined(err));
+        let Error::Auth(AuthenticationError::AuthorizationDeclined(details)) = err else {
+            panic!("unexpected {:?}", err);
+        };
+        assert_eq!(details.trace_id.as_deref(), Some("trace"));
+        assert_eq!(details.correlation_id.as_deref(), Some("correlation"));
+    }
+
+    #[test]
+    fn local_oauth_error_has_no_ids() {
+        let err = OAuthError::new("expired");
+        assert_eq!(err.trace_id, None);
+        assert_eq!(err.correlation_id, None);
+    }
+
+    async fn request_error(client: &reqwest::Client, url: &str) -> reqwest::Error {
+        client.get(url).send().await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn closed_port_is_a_connect_error() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let err = request_error(
+            &reqwest::Client::new(),
+            &format!("http://127.0.0.1:{}", port),
+        )
+        .await;
+        assert_eq!(NetworkErrorKind::of(&err), NetworkErrorKind::Connect);
+    }
+
+    #[tokio::test]
+    async fn slow_server_is_a_timeout() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::any())
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(5)),
+            )
+            .mount(&server)
+            .await;
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let err = request_error(&client, &server.uri()).await;
+        assert_eq!(NetworkErrorKind::of(&err), NetworkErrorKind::Timeout);
+    }
+
+    #[tokio::test]
+    async fn https_to_a_plain_server_is_a_tls_error() {
+        let server = wiremock::MockServer::start().await;
+        let url = server.uri().replacen("http://", "https://", 1);
+        let err = request_error(&reqwest::Client::new(), &url).await;
+        assert!(err.is_connect(), "{:?}", err);
+        assert_eq!(
+            NetworkErrorKind::of(&err),
+            NetworkErrorKind::Tls,
+            "{:?}",
+            err
+        );
+    }
 }
@@ -1,8 +1,11 @@
 pub mod auth;
-mod client;
+#[cfg(feature = "sqlite")]
+pub mod cache;
+pub mod client;
 pub mod error;
+pub mod models;
+pub mod prelude;
 pub mod utils;
 
-pub fn greet() {
-    println!("Hello worlf")
-}
+#[cfg(test)]
+mod testing;
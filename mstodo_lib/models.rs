@@ -0,0 +1,1414 @@
+//! Resources returned by and sent to the To Do API
+
+/// Timestamp set by Graph, e.g. `created_date_time`.
+/// Parsed into `chrono::DateTime<Utc>` with the `chrono` feature,
+/// kept as the raw ISO-8601 string otherwise
+#[cfg(feature = "chrono")]
+pub type GraphDateTime = chrono::DateTime<chrono::Utc>;
+#[cfg(not(feature = "chrono"))]
+pub type GraphDateTime = String;
+
+/// A list of tasks, e.g. the default "Tasks" list
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/todotasklist"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoTaskList {
+    pub id: String,
+    pub display_name: String,
+    pub is_owner: bool,
+    pub is_shared: bool,
+    /// Graph spells it `wellknownListName`
+    #[serde(rename = "wellknownListName", alias = "wellKnownListName", default)]
+    pub well_known_list_name: WellKnownListName,
+}
+
+/// Built-in lists, created by To Do rather than the user
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WellKnownListName {
+    /// A list created by the user
+    #[default]
+    None,
+    /// The "Tasks" list
+    DefaultList,
+    /// The list of flagged Outlook emails
+    FlaggedEmails,
+    /// Any value unknown to this crate
+    #[serde(other)]
+    Other,
+}
+
+impl WellKnownListName {
+    pub fn is_default(&self) -> bool {
+        *self == Self::DefaultList
+    }
+
+    pub fn is_flagged_emails(&self) -> bool {
+        *self == Self::FlaggedEmails
+    }
+}
+
+/// Someone a shared task list is shared with, see [crate::client::TodoClient::list_members]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListMember {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub user_principal_name: Option<String>,
+}
+
+/// The signed-in user, as returned by `/me`
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/user"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub id: String,
+    pub display_name: Option<String>,
+    /// Name used to sign in, usually an email address
+    pub user_principal_name: String,
+    pub mail: Option<String>,
+}
+
+/// Progress of a task.
+/// Values unknown to this crate are kept in [TaskStatus::Unknown]
+/// so new ones do not break deserialization.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+pub enum TaskStatus {
+    NotStarted,
+    InProgress,
+    Completed,
+    WaitingOnOthers,
+    Deferred,
+    Unknown(String),
+}
+
+impl TaskStatus {
+    /// Value used by Graph
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::NotStarted => "notStarted",
+            Self::InProgress => "inProgress",
+            Self::Completed => "completed",
+            Self::WaitingOnOthers => "waitingOnOthers",
+            Self::Deferred => "deferred",
+            Self::Unknown(status) => status,
+        }
+    }
+}
+
+impl From<String> for TaskStatus {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "notStarted" => Self::NotStarted,
+            "inProgress" => Self::InProgress,
+            "completed" => Self::Completed,
+            "waitingOnOthers" => Self::WaitingOnOthers,
+            "deferred" => Self::Deferred,
+            _ => Self::Unknown(status),
+        }
+    }
+}
+
+impl From<TaskStatus> for String {
+    fn from(status: TaskStatus) -> Self {
+        match status {
+            TaskStatus::Unknown(status) => status,
+            status => status.as_str().to_owned(),
+        }
+    }
+}
+
+/// Values unknown to this crate are kept in [Importance::Unknown]
+/// so new ones do not break deserialization.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+pub enum Importance {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Unknown(String),
+}
+
+impl Importance {
+    /// Value used by Graph
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+            Self::Unknown(importance) => importance,
+        }
+    }
+}
+
+impl From<String> for Importance {
+    fn from(importance: String) -> Self {
+        match importance.as_str() {
+            "low" => Self::Low,
+            "normal" => Self::Normal,
+            "high" => Self::High,
+            _ => Self::Unknown(importance),
+        }
+    }
+}
+
+impl From<Importance> for String {
+    fn from(importance: Importance) -> Self {
+        match importance {
+            Importance::Unknown(importance) => importance,
+            importance => importance.as_str().to_owned(),
+        }
+    }
+}
+
+/// A task in a [TodoTaskList]
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/todotask"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoTask {
+    pub id: String,
+    /// Version of the task, for `If-Match` in [crate::client::TodoClient::update_task]
+    #[serde(
+        rename = "@odata.etag",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub etag: Option<String>,
+    pub title: String,
+    pub status: TaskStatus,
+    pub importance: Importance,
+    #[cfg_attr(
+        feature = "chrono",
+        serde(
+            serialize_with = "crate::utils::serialize_graph_datetime",
+            deserialize_with = "crate::utils::deserialize_graph_datetime"
+        )
+    )]
+    pub created_date_time: GraphDateTime,
+    #[cfg_attr(
+        feature = "chrono",
+        serde(
+            serialize_with = "crate::utils::serialize_graph_datetime",
+            deserialize_with = "crate::utils::deserialize_graph_datetime"
+        )
+    )]
+    pub last_modified_date_time: GraphDateTime,
+    pub due_date_time: Option<DateTimeTimeZone>,
+    pub completed_date_time: Option<DateTimeTimeZone>,
+    /// [None] when the task has no body, Graph sending an empty one
+    #[serde(default, deserialize_with = "deserialize_body")]
+    pub body: Option<ItemBody>,
+    pub is_reminder_on: bool,
+    pub reminder_date_time: Option<DateTimeTimeZone>,
+    pub recurrence: Option<Recurrence>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+impl TodoTask {
+    /// Whether the task is starred, which To Do stores as a high importance
+    pub fn is_important(&self) -> bool {
+        self.importance == Importance::High
+    }
+
+    /// Headers of the columns returned by [Self::to_table_row]
+    pub const TABLE_HEADERS: [&'static str; 5] = ["ID", "STATUS", "IMPORTANCE", "DUE", "TITLE"];
+
+    /// Fields shown when printing tasks as columns, see [Self::TABLE_HEADERS]
+    pub fn to_table_row(&self) -> [String; 5] {
+        [
+            self.id.clone(),
+            self.status.as_str().to_owned(),
+            self.importance.as_str().to_owned(),
+            self.due_date().unwrap_or_default().to_owned(),
+            self.title.clone(),
+        ]
+    }
+
+    /// Day part of the due date, e.g. `2024-05-01`
+    fn due_date(&self) -> Option<&str> {
+        let due = self.due_date_time.as_ref()?;
+        due.date_time.split('T').next()
+    }
+}
+
+/// A single line such as `[ ] Title (due 2024-05-01, high)`,
+/// the box being checked once the task is completed
+impl std::fmt::Display for TodoTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mark = if self.status == TaskStatus::Completed {
+            'x'
+        } else {
+            ' '
+        };
+        write!(f, "[{}] {}", mark, self.title)?;
+        let mut details = Vec::new();
+        if let Some(due) = self.due_date() {
+            details.push(format!("due {}", due));
+        }
+        if self.importance != Importance::Normal {
+            details.push(self.importance.as_str().to_owned());
+        }
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Order applied by [sort_tasks]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Most important first
+    Importance,
+    /// Earliest due first, tasks without a due date last
+    DueDate,
+    /// Oldest first
+    CreatedDate,
+}
+
+/// Sorts tasks by `key`, then by title
+pub fn sort_tasks(tasks: &mut [TodoTask], key: SortKey) {
+    tasks.sort_by(|a, b| {
+        let by_key = match key {
+            SortKey::Importance => {
+                importance_rank(&a.importance).cmp(&importance_rank(&b.importance))
+            }
+            SortKey::DueDate => {
+                let due =
+                    |task: &TodoTask| task.due_date_time.as_ref().map(|due| due.date_time.clone());
+                match (due(a), due(b)) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => std::cmp::Ordering::Equal,
+                }
+            }
+            SortKey::CreatedDate => a.created_date_time.cmp(&b.created_date_time),
+        };
+        by_key.then_with(|| a.title.cmp(&b.title))
+    });
+}
+
+/// Lower is more important
+fn importance_rank(importance: &Importance) -> u8 {
+    match importance {
+        Importance::High => 0,
+        Importance::Normal => 1,
+        Importance::Low => 2,
+        Importance::Unknown(_) => 3,
+    }
+}
+
+/// A subtask of a [TodoTask]
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/checklistitem"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecklistItem {
+    pub id: String,
+    pub display_name: String,
+    pub is_checked: bool,
+    #[cfg_attr(
+        feature = "chrono",
+        serde(
+            serialize_with = "crate::utils::serialize_graph_datetime",
+            deserialize_with = "crate::utils::deserialize_graph_datetime"
+        )
+    )]
+    pub created_date_time: GraphDateTime,
+}
+
+/// Link from a [TodoTask] back to the item it was created from in another app
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/linkedresource"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedResource {
+    pub id: String,
+    pub web_url: Option<String>,
+    pub application_name: Option<String>,
+    pub display_name: Option<String>,
+    pub external_id: Option<String>,
+}
+
+/// Linked resource to be created with [crate::client::TodoClient::create_linked_resource]
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewLinkedResource {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+    pub application_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+}
+
+pub(crate) const UTC: &str = "UTC";
+
+/// A point in time in a given time zone, e.g. a due date
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/datetimetimezone"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DateTimeTimeZone {
+    /// e.g. `2024-05-01T00:00:00`
+    pub date_time: String,
+    pub time_zone: String,
+}
+
+impl DateTimeTimeZone {
+    pub fn new(date_time: &str, time_zone: &str) -> Self {
+        Self {
+            date_time: date_time.to_owned(),
+            time_zone: time_zone.to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl DateTimeTimeZone {
+    pub fn utc(date_time: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            date_time: date_time.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+            time_zone: UTC.to_owned(),
+        }
+    }
+
+    /// Only UTC is supported as Graph uses Windows time zone names,
+    /// which have no direct equivalent in `chrono`
+    pub fn to_chrono(&self) -> Result<chrono::DateTime<chrono::Utc>, crate::error::Error> {
+        if !matches!(
+            self.time_zone.as_str(),
+            "UTC" | "Etc/UTC" | "Coordinated Universal Time"
+        ) {
+            return Err(
+                crate::error::DateTimeError::UnsupportedTimeZone(self.time_zone.clone()).into(),
+            );
+        }
+        let naive = chrono::NaiveDateTime::parse_from_str(&self.date_time, "%Y-%m-%dT%H:%M:%S%.f")
+            .map_err(|_| crate::error::DateTimeError::InvalidDateTime(self.date_time.clone()))?;
+        Ok(naive.and_utc())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for DateTimeTimeZone {
+    fn from(date_time: chrono::DateTime<chrono::Utc>) -> Self {
+        Self::utc(date_time)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<&DateTimeTimeZone> for chrono::DateTime<chrono::Utc> {
+    type Error = crate::error::Error;
+
+    fn try_from(date_time: &DateTimeTimeZone) -> Result<Self, Self::Error> {
+        date_time.to_chrono()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecurrencePatternType {
+    Daily,
+    Weekly,
+    AbsoluteMonthly,
+    RelativeMonthly,
+    AbsoluteYearly,
+    RelativeYearly,
+}
+
+/// How often a task repeats
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/recurrencepattern"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurrencePattern {
+    #[serde(rename = "type")]
+    pub pattern_type: RecurrencePatternType,
+    pub interval: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days_of_week: Vec<Weekday>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub day_of_month: u32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub month: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecurrenceRangeType {
+    NoEnd,
+    EndDate,
+    Numbered,
+}
+
+/// When a task stops repeating
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/recurrencerange"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurrenceRange {
+    #[serde(rename = "type")]
+    pub range_type: RecurrenceRangeType,
+    /// e.g. `2024-05-01`
+    pub start_date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub number_of_occurrences: u32,
+}
+
+/// Repetition of a task, `patternedRecurrence` in Graph
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/patternedrecurrence"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub pattern: RecurrencePattern,
+    pub range: RecurrenceRange,
+}
+
+impl Recurrence {
+    /// Repeats every `interval` days, starting today without an end
+    pub fn daily(interval: u32) -> Self {
+        Self::new(RecurrencePatternType::Daily, interval)
+    }
+
+    /// Repeats on `days` every `interval` weeks, starting today without an end
+    pub fn weekly(days: &[Weekday], interval: u32) -> Self {
+        let mut recurrence = Self::new(RecurrencePatternType::Weekly, interval);
+        recurrence.pattern.days_of_week = days.to_vec();
+        recurrence
+    }
+
+    /// Repeats on `day_of_month` every `interval` months, starting today without an end
+    pub fn monthly(day_of_month: u32, interval: u32) -> Self {
+        let mut recurrence = Self::new(RecurrencePatternType::AbsoluteMonthly, interval);
+        recurrence.pattern.day_of_month = day_of_month;
+        recurrence
+    }
+
+    /// Repeats on `day_of_month` of `month` every `interval` years, starting today without an end
+    pub fn yearly(month: u32, day_of_month: u32, interval: u32) -> Self {
+        let mut recurrence = Self::new(RecurrencePatternType::AbsoluteYearly, interval);
+        recurrence.pattern.month = month;
+        recurrence.pattern.day_of_month = day_of_month;
+        recurrence
+    }
+
+    fn new(pattern_type: RecurrencePatternType, interval: u32) -> Self {
+        let today = crate::utils::format_utc(std::time::SystemTime::now());
+        Self {
+            pattern: RecurrencePattern {
+                pattern_type,
+                interval,
+                days_of_week: Vec::new(),
+                day_of_month: 0,
+                month: 0,
+            },
+            range: RecurrenceRange {
+                range_type: RecurrenceRangeType::NoEnd,
+                start_date: today[..10].to_owned(),
+                end_date: None,
+                number_of_occurrences: 0,
+            },
+        }
+    }
+
+    /// `start_date` being e.g. `2024-05-01`
+    pub fn starting(mut self, start_date: &str) -> Self {
+        self.range.start_date = start_date.to_owned();
+        self
+    }
+
+    /// Stops repeating after `end_date`, e.g. `2024-12-31`
+    pub fn until(mut self, end_date: &str) -> Self {
+        self.range.range_type = RecurrenceRangeType::EndDate;
+        self.range.end_date = Some(end_date.to_owned());
+        self.range.number_of_occurrences = 0;
+        self
+    }
+
+    /// Stops repeating after `occurrences` times
+    pub fn occurrences(mut self, occurrences: u32) -> Self {
+        self.range.range_type = RecurrenceRangeType::Numbered;
+        self.range.number_of_occurrences = occurrences;
+        self.range.end_date = None;
+        self
+    }
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Task to be created with [crate::client::TodoClient::create_task].
+/// Deserializes from the same shape, only `title` being required.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewTask {
+    pub title: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_body"
+    )]
+    pub body: Option<ItemBody>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date_time: Option<DateTimeTimeZone>,
+    #[serde(default)]
+    pub importance: Importance,
+    /// Defaults to `true` when `reminder_date_time` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_reminder_on: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reminder_date_time: Option<DateTimeTimeZone>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    /// Names of categories from the user's master category list
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+}
+
+impl NewTask {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_owned(),
+            body: None,
+            due_date_time: None,
+            importance: Importance::Normal,
+            is_reminder_on: None,
+            reminder_date_time: None,
+            recurrence: None,
+            categories: Vec::new(),
+        }
+    }
+
+    /// Plain text body, see [ItemBody::html] for HTML
+    pub fn body(mut self, body: impl Into<ItemBody>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Due date in UTC, `date_time` being e.g. `2024-05-01T00:00:00`
+    pub fn due(self, date_time: &str) -> Self {
+        self.due_in_time_zone(date_time, UTC)
+    }
+
+    pub fn due_in_time_zone(mut self, date_time: &str, time_zone: &str) -> Self {
+        self.due_date_time = Some(DateTimeTimeZone::new(date_time, time_zone));
+        self
+    }
+
+    pub fn importance(mut self, importance: Importance) -> Self {
+        self.importance = importance;
+        self
+    }
+
+    /// Reminder in UTC, `date_time` being e.g. `2024-05-01T09:00:00`
+    pub fn reminder(mut self, date_time: &str) -> Self {
+        self.reminder_date_time = Some(DateTimeTimeZone::new(date_time, UTC));
+        self
+    }
+
+    pub fn reminder_on(mut self, is_reminder_on: bool) -> Self {
+        self.is_reminder_on = Some(is_reminder_on);
+        self
+    }
+
+    pub fn recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+
+    pub fn categories(mut self, categories: &[&str]) -> Self {
+        self.categories = categories.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    /// Turns the reminder on when a reminder date is set
+    /// and it was not explicitly turned off
+    pub(crate) fn couple_reminder(mut self) -> Self {
+        if self.reminder_date_time.is_some() {
+            self.is_reminder_on.get_or_insert(true);
+        }
+        self
+    }
+}
+
+/// Copy of an existing task, without its status or checklist
+impl From<&TodoTask> for NewTask {
+    fn from(task: &TodoTask) -> Self {
+        Self {
+            title: task.title.clone(),
+            body: task.body.clone(),
+            due_date_time: task.due_date_time.clone(),
+            importance: task.importance.clone(),
+            is_reminder_on: Some(task.is_reminder_on),
+            reminder_date_time: task.reminder_date_time.clone(),
+            recurrence: task.recurrence.clone(),
+            categories: task.categories.clone(),
+        }
+    }
+}
+
+/// Partial update of a task with [crate::client::TodoClient::update_task].
+/// Only the fields that are [Some] are sent, the others are left untouched.
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub importance: Option<Importance>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_date_time: Option<DateTimeTimeZone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<ItemBody>,
+    /// Defaults to `true` when `reminder_date_time` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_reminder_on: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reminder_date_time: Option<DateTimeTimeZone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    /// Replaces every category of the task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub categories: Option<Vec<String>>,
+}
+
+impl TaskPatch {
+    /// Turns the reminder on when a reminder date is set
+    /// and it was not explicitly turned off
+    pub(crate) fn couple_reminder(mut self) -> Self {
+        if self.reminder_date_time.is_some() {
+            self.is_reminder_on.get_or_insert(true);
+        }
+        self
+    }
+}
+
+/// Content of a task body
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/itembody"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemBody {
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub content_type: BodyType,
+}
+
+impl ItemBody {
+    pub fn text(content: &str) -> Self {
+        Self {
+            content: content.to_owned(),
+            content_type: BodyType::Text,
+        }
+    }
+
+    /// HTML is sent and returned as is, without any sanitizing,
+    /// so bodies read from Graph must be treated as untrusted markup
+    pub fn html(content: &str) -> Self {
+        Self {
+            content: content.to_owned(),
+            content_type: BodyType::Html,
+        }
+    }
+}
+
+impl From<&str> for ItemBody {
+    fn from(content: &str) -> Self {
+        Self::text(content)
+    }
+}
+
+impl From<String> for ItemBody {
+    fn from(content: String) -> Self {
+        Self {
+            content,
+            content_type: BodyType::Text,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyType {
+    #[default]
+    Text,
+    Html,
+}
+
+/// Reads an `itemBody`, an empty one being no body
+fn deserialize_body<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<ItemBody>, D::Error> {
+    let body: Option<ItemBody> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(body.filter(|body| !body.content.is_empty()))
+}
+
+/// File attached to a [TodoTask]
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/taskfileattachment"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskFileAttachment {
+    pub id: String,
+    pub name: String,
+    pub content_type: String,
+    /// In bytes
+    pub size: u64,
+}
+
+/// A category of the user's master category list, which tasks refer to by name
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/outlookcategory"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OutlookCategory {
+    pub id: String,
+    pub display_name: String,
+    /// One of the preset colors, e.g. `preset0`, or `none`
+    pub color: String,
+}
+
+/// Changes to the tasks of a list since the previous delta query
+/// See "https://learn.microsoft.com/en-us/graph/api/todotask-delta"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DeltaPage {
+    /// Tasks created or updated
+    pub tasks: Vec<TodoTask>,
+    /// Ids of the deleted tasks
+    pub removed: Vec<String>,
+    /// Link to persist and pass to the next delta query
+    pub delta_link: Option<String>,
+}
+
+/// Subscription to be created with [crate::client::TodoClient::create_subscription]
+/// See "https://learn.microsoft.com/en-us/graph/api/subscription-post-subscriptions"
+/// for more information
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionRequest {
+    /// Comma separated changes to be notified of, e.g. `created,updated`
+    pub change_type: String,
+    /// Endpoint receiving the notifications. It must answer the validation
+    /// request Graph sends on creation, which this crate does not handle.
+    pub notification_url: String,
+    pub resource: String,
+    /// e.g. `2024-05-01T00:00:00Z`, at most a few days ahead
+    pub expiration_date_time: String,
+    /// Secret sent back in every notification to prove where it comes from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_state: Option<String>,
+}
+
+impl SubscriptionRequest {
+    /// Subscribes to every change to the tasks of a list
+    pub fn for_list(list_id: &str, notification_url: &str, expiration_date_time: &str) -> Self {
+        Self {
+            change_type: "created,updated,deleted".to_owned(),
+            notification_url: notification_url.to_owned(),
+            resource: format!("/me/todo/lists/{}/tasks", list_id),
+            expiration_date_time: expiration_date_time.to_owned(),
+            client_state: None,
+        }
+    }
+
+    pub fn client_state(mut self, client_state: &str) -> Self {
+        self.client_state = Some(client_state.to_owned());
+        self
+    }
+}
+
+/// Change notification subscription
+/// See "https://learn.microsoft.com/en-us/graph/api/resources/subscription"
+/// for more information
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    pub id: String,
+    pub resource: String,
+    pub change_type: String,
+    pub notification_url: String,
+    #[cfg_attr(
+        feature = "chrono",
+        serde(
+            serialize_with = "crate::utils::serialize_graph_datetime",
+            deserialize_with = "crate::utils::deserialize_graph_datetime"
+        )
+    )]
+    pub expiration_date_time: GraphDateTime,
+    pub client_state: Option<String>,
+}
+
+/// Sub-request of [crate::client::TodoClient::batch]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRequest {
+    pub method: reqwest::Method,
+    /// Relative to the Graph API root, e.g. `/me/todo/lists`
+    pub url: String,
+    pub body: Option<serde_json::Value>,
+}
+
+impl BatchRequest {
+    pub fn new(method: reqwest::Method, url: &str) -> Self {
+        Self {
+            method,
+            url: url.to_owned(),
+            body: None,
+        }
+    }
+
+    pub fn get(url: &str) -> Self {
+        Self::new(reqwest::Method::GET, url)
+    }
+
+    pub fn delete(url: &str) -> Self {
+        Self::new(reqwest::Method::DELETE, url)
+    }
+
+    /// POST of `body` serialized as JSON
+    pub fn post<T: serde::Serialize>(url: &str, body: &T) -> Result<Self, crate::error::Error> {
+        Self::new(reqwest::Method::POST, url).json(body)
+    }
+
+    /// PATCH of `body` serialized as JSON
+    pub fn patch<T: serde::Serialize>(url: &str, body: &T) -> Result<Self, crate::error::Error> {
+        Self::new(reqwest::Method::PATCH, url).json(body)
+    }
+
+    pub fn json<T: serde::Serialize>(mut self, body: &T) -> Result<Self, crate::error::Error> {
+        self.body = Some(serde_json::to_value(body)?);
+        Ok(self)
+    }
+}
+
+/// Response to a [BatchRequest]
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct BatchResponse {
+    pub id: String,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: std::collections::HashMap<String, String>,
+    pub body: Option<serde_json::Value>,
+}
+
+impl BatchResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Deserializes the body of a successful response,
+    /// failing the way a standalone request would otherwise
+    pub fn parse<T: serde::de::DeserializeOwned>(self) -> Result<T, crate::error::Error> {
+        let is_success = self.is_success();
+        let body = self.body.unwrap_or_default();
+        if !is_success {
+            let status = reqwest::StatusCode::from_u16(self.status)
+                .map_err(|_| crate::error::Error::UnexpectedResponse(body.to_string()))?;
+            return Err(crate::client::status_error(status, body.to_string()));
+        }
+        Ok(serde_json::from_value(body)?)
+    }
+}
+
+/// Body of a `$batch` response
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct BatchResponseBody {
+    pub responses: Vec<BatchResponse>,
+}
+
+/// Item of a delta response, removed items only carry their id
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub(crate) enum DeltaItem {
+    Removed {
+        id: String,
+        #[serde(rename = "@removed")]
+        removed: serde_json::Value,
+    },
+    Task(Box<TodoTask>),
+}
+
+/// Envelope of every collection returned by Graph, e.g. for [crate::client::TodoClient::raw_get].
+/// Collections are paged, `next_link` points to the next page if there is one.
+/// The last page of a delta query carries a `delta_link` instead.
+/// See "https://learn.microsoft.com/en-us/graph/paging"
+/// for more information
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct GraphCollection<T> {
+    pub value: Vec<T>,
+    #[serde(rename = "@odata.nextLink")]
+    pub next_link: Option<String>,
+    #[serde(rename = "@odata.deltaLink")]
+    pub delta_link: Option<String>,
+    /// Total number of items, only sent when asked with `$count=true`
+    #[serde(rename = "@odata.count")]
+    pub count: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn task_list_from_graph() {
+        let list: TodoTaskList = serde_json::from_value(json!({
+            "@odata.etag": "W/\"m1fdwWoFiE2YS9yegTKoYwAA/ZGlTQ==\"",
+            "displayName": "Tasks",
+            "isOwner": true,
+            "isShared": false,
+            "wellknownListName": "defaultList",
+            "id": "AAMkADIyAAAAABrJAAA=",
+        }))
+        .unwrap();
+        assert_eq!(
+            list,
+            TodoTaskList {
+                id: "AAMkADIyAAAAABrJAAA=".to_owned(),
+                display_name: "Tasks".to_owned(),
+                is_owner: true,
+                is_shared: false,
+                well_known_list_name: WellKnownListName::DefaultList,
+            }
+        );
+    }
+
+    #[test]
+    fn task_list_round_trip() {
+        let list: TodoTaskList = serde_json::from_value(json!({
+            "id": "1",
+            "displayName": "Groceries",
+            "isOwner": false,
+            "isShared": true,
+            "wellKnownListName": "none",
+        }))
+        .unwrap();
+        assert_eq!(list.well_known_list_name, WellKnownListName::None);
+        let value = serde_json::to_value(&list).unwrap();
+        assert_eq!(value["wellknownListName"], "none");
+        assert_eq!(serde_json::from_value::<TodoTaskList>(value).unwrap(), list);
+    }
+
+    /// Task as returned by Graph with the given status
+    fn task_json(id: &str, status: &str) -> serde_json::Value {
+        json!({
+            "@odata.etag": "W/\"etag\"",
+            "importance": "normal",
+            "isReminderOn": false,
+            "status": status,
+            "title": format!("Task {}", id),
+            "createdDateTime": "2024-05-01T08:30:00.1234567Z",
+            "lastModifiedDateTime": "2024-05-02T08:30:00.1234567Z",
+            "categories": [],
+            "id": id,
+            "body": { "content": "", "contentType": "text" },
+        })
+    }
+
+    #[test]
+    fn tasks_with_every_status() {
+        let statuses = [
+            ("notStarted", TaskStatus::NotStarted),
+            ("inProgress", TaskStatus::InProgress),
+            ("completed", TaskStatus::Completed),
+            ("waitingOnOthers", TaskStatus::WaitingOnOthers),
+            ("deferred", TaskStatus::Deferred),
+        ];
+        let payload: Vec<serde_json::Value> = statuses
+            .iter()
+            .enumerate()
+            .map(|(i, (status, _))| task_json(&i.to_string(), status))
+            .collect();
+        let tasks: Vec<TodoTask> = serde_json::from_value(payload.into()).unwrap();
+        for (task, (_, status)) in tasks.iter().zip(statuses) {
+            assert_eq!(task.status, status);
+            assert_eq!(task.body, None);
+            assert_eq!(task.etag.as_deref(), Some("W/\"etag\""));
+        }
+    }
+
+    #[test]
+    fn new_task_json() {
+        let new_task = NewTask::new("Pay rent")
+            .due("2024-05-01T00:00:00")
+            .importance(Importance::High)
+            .body("Before noon");
+        assert_eq!(
+            serde_json::to_value(&new_task).unwrap(),
+            json!({
+                "title": "Pay rent",
+                "body": { "content": "Before noon", "contentType": "text" },
+                "dueDateTime": { "dateTime": "2024-05-01T00:00:00", "timeZone": "UTC" },
+                "importance": "high",
+            })
+        );
+    }
+
+    #[test]
+    fn minimal_new_task_json() {
+        assert_eq!(
+            serde_json::to_value(NewTask::new("Pay rent")).unwrap(),
+            json!({ "title": "Pay rent", "importance": "normal" })
+        );
+    }
+
+    #[test]
+    fn empty_patch_sends_nothing() {
+        assert_eq!(
+            serde_json::to_value(TaskPatch::default()).unwrap(),
+            json!({})
+        );
+    }
+
+    #[test]
+    fn patch_only_sends_the_set_fields() {
+        let patch = TaskPatch {
+            title: Some("Buy oat milk".to_owned()),
+            ..TaskPatch::default()
+        };
+        assert_eq!(
+            serde_json::to_value(patch).unwrap(),
+            json!({ "title": "Buy oat milk" })
+        );
+    }
+
+    #[test]
+    fn linked_resources() {
+        let resources: Vec<LinkedResource> = serde_json::from_value(json!([
+            {
+                "id": "r1",
+                "webUrl": "https://outlook.office.com/mail/id/AAMk",
+                "applicationName": "Outlook",
+                "displayName": "Invoice",
+                "externalId": "AAMk",
+            },
+            { "id": "r2", "applicationName": "App" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            resources[0],
+            LinkedResource {
+                id: "r1".to_owned(),
+                web_url: Some("https://outlook.office.com/mail/id/AAMk".to_owned()),
+                application_name: Some("Outlook".to_owned()),
+                display_name: Some("Invoice".to_owned()),
+                external_id: Some("AAMk".to_owned()),
+            }
+        );
+        assert_eq!(resources[1].application_name.as_deref(), Some("App"));
+        assert_eq!(resources[1].web_url, None);
+        assert_eq!(resources[1].display_name, None);
+        assert_eq!(resources[1].external_id, None);
+    }
+
+    #[test]
+    fn reminder_turns_the_reminder_on() {
+        let new_task = NewTask::new("Pay rent")
+            .reminder("2024-05-01T09:00:00")
+            .couple_reminder();
+        assert_eq!(
+            serde_json::to_value(new_task).unwrap(),
+            json!({
+                "title": "Pay rent",
+                "importance": "normal",
+                "isReminderOn": true,
+                "reminderDateTime": { "dateTime": "2024-05-01T09:00:00", "timeZone": "UTC" },
+            })
+        );
+    }
+
+    #[test]
+    fn reminder_turned_off_stays_off() {
+        let new_task = NewTask::new("Pay rent")
+            .reminder("2024-05-01T09:00:00")
+            .reminder_on(false)
+            .couple_reminder();
+        assert_eq!(new_task.is_reminder_on, Some(false));
+
+        let patch = TaskPatch {
+            reminder_date_time: Some(DateTimeTimeZone {
+                date_time: "2024-05-01T09:00:00".to_owned(),
+                time_zone: UTC.to_owned(),
+            }),
+            ..TaskPatch::default()
+        }
+        .couple_reminder();
+        assert_eq!(patch.is_reminder_on, Some(true));
+        assert_eq!(TaskPatch::default().couple_reminder(), TaskPatch::default());
+    }
+
+    #[test]
+    fn daily_recurrence_json() {
+        let recurrence = Recurrence::daily(2).starting("2024-05-01");
+        let value = json!({
+            "pattern": { "type": "daily", "interval": 2 },
+            "range": { "type": "noEnd", "startDate": "2024-05-01" },
+        });
+        assert_eq!(serde_json::to_value(&recurrence).unwrap(), value);
+        assert_eq!(
+            serde_json::from_value::<Recurrence>(value).unwrap(),
+            recurrence
+        );
+    }
+
+    #[test]
+    fn weekly_recurrence_json() {
+        let recurrence = Recurrence::weekly(&[Weekday::Monday, Weekday::Thursday], 1)
+            .starting("2024-05-01")
+            .occurrences(10);
+        let value = json!({
+            "pattern": {
+                "type": "weekly",
+                "interval": 1,
+                "daysOfWeek": ["monday", "thursday"],
+            },
+            "range": {
+                "type": "numbered",
+                "startDate": "2024-05-01",
+                "numberOfOccurrences": 10,
+            },
+        });
+        assert_eq!(serde_json::to_value(&recurrence).unwrap(), value);
+        assert_eq!(
+            serde_json::from_value::<Recurrence>(value).unwrap(),
+            recurrence
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn date_time_time_zone_chrono_round_trip() {
+        use chrono::{TimeZone, Timelike};
+
+        let date_time = chrono::Utc
+            .with_ymd_and_hms(2024, 5, 1, 8, 30, 0)
+            .unwrap()
+            .with_nanosecond(123_456_700)
+            .unwrap();
+        let converted = DateTimeTimeZone::from(date_time);
+        assert_eq!(converted.time_zone, "UTC");
+        assert_eq!(converted.to_chrono().unwrap(), date_time);
+        assert_eq!(
+            chrono::DateTime::try_from(&DateTimeTimeZone::new("2024-05-01T08:30:00", UTC)).unwrap(),
+            chrono::Utc.with_ymd_and_hms(2024, 5, 1, 8, 30, 0).unwrap()
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn date_time_time_zone_to_chrono_errors() {
+        let err = DateTimeTimeZone::new("2024-05-01T08:30:00", "Pacific Standard Time")
+            .to_chrono()
+            .unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                crate::error::Error::DateTime(crate::error::DateTimeError::UnsupportedTimeZone(tz))
+                    if tz == "Pacific Standard Time"
+            ),
+            "{:?}",
+            err
+        );
+        let err = DateTimeTimeZone::new("next tuesday", UTC)
+            .to_chrono()
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                crate::error::Error::DateTime(crate::error::DateTimeError::InvalidDateTime(_))
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn status_values_round_trip() {
+        let statuses = [
+            ("notStarted", TaskStatus::NotStarted),
+            ("inProgress", TaskStatus::InProgress),
+            ("completed", TaskStatus::Completed),
+            ("waitingOnOthers", TaskStatus::WaitingOnOthers),
+            ("deferred", TaskStatus::Deferred),
+            ("blocked", TaskStatus::Unknown("blocked".to_owned())),
+        ];
+        for (value, status) in statuses {
+            assert_eq!(
+                serde_json::from_value::<TaskStatus>(json!(value)).unwrap(),
+                status
+            );
+            assert_eq!(serde_json::to_value(&status).unwrap(), json!(value));
+        }
+    }
+
+    #[test]
+    fn importance_values_round_trip() {
+        let importances = [
+            ("low", Importance::Low),
+            ("normal", Importance::Normal),
+            ("high", Importance::High),
+            ("urgent", Importance::Unknown("urgent".to_owned())),
+        ];
+        for (value, importance) in importances {
+            assert_eq!(
+                serde_json::from_value::<Importance>(json!(value)).unwrap(),
+                importance
+            );
+            assert_eq!(serde_json::to_value(&importance).unwrap(), json!(value));
+        }
+    }
+
+    #[test]
+    fn text_and_html_bodies() {
+        assert_eq!(
+            serde_json::to_value(ItemBody::text("Before noon")).unwrap(),
+            json!({ "content": "Before noon", "contentType": "text" })
+        );
+        assert_eq!(
+            serde_json::to_value(ItemBody::html("<p>Before <b>noon</b></p>")).unwrap(),
+            json!({ "content": "<p>Before <b>noon</b></p>", "contentType": "html" })
+        );
+        let new_task = NewTask::new("Pay rent").body(ItemBody::html("<p>Before noon</p>"));
+        assert_eq!(
+            serde_json::to_value(new_task).unwrap()["body"],
+            json!({ "content": "<p>Before noon</p>", "contentType": "html" })
+        );
+        let body: ItemBody =
+            serde_json::from_value(json!({ "content": "<p>hi</p>", "contentType": "html" }))
+                .unwrap();
+        assert_eq!(body, ItemBody::html("<p>hi</p>"));
+    }
+
+    #[test]
+    fn sort_tasks_by_each_key() {
+        let task = |id: &str, importance: &str, due: Option<&str>, created: &str| {
+            let mut task = task_json(id, "notStarted");
+            task["importance"] = importance.into();
+            task["createdDateTime"] = created.into();
+            if let Some(due) = due {
+                task["dueDateTime"] = json!({ "dateTime": due, "timeZone": "UTC" });
+            }
+            serde_json::from_value::<TodoTask>(task).unwrap()
+        };
+        let mut tasks = vec![
+            task("1", "low", None, "2024-05-03T00:00:00Z"),
+            task(
+                "2",
+                "high",
+                Some("2024-06-01T00:00:00"),
+                "2024-05-02T00:00:00Z",
+            ),
+            task(
+                "3",
+                "normal",
+                Some("2024-05-15T00:00:00"),
+                "2024-05-04T00:00:00Z",
+            ),
+            task("4", "high", None, "2024-05-01T00:00:00Z"),
+        ];
+        let ids = |tasks: &[TodoTask]| -> Vec<String> {
+            tasks.iter().map(|task| task.id.clone()).collect()
+        };
+
+        sort_tasks(&mut tasks, SortKey::Importance);
+        assert_eq!(ids(&tasks), ["2", "4", "3", "1"]);
+        sort_tasks(&mut tasks, SortKey::DueDate);
+        assert_eq!(ids(&tasks), ["3", "2", "1", "4"]);
+        sort_tasks(&mut tasks, SortKey::CreatedDate);
+        assert_eq!(ids(&tasks), ["4", "2", "1", "3"]);
+    }
+
+    #[test]
+    fn display_completed_and_open_tasks() {
+        let completed: TodoTask = serde_json::from_value(task_json("1", "completed")).unwrap();
+        assert_eq!(completed.to_string(), "[x] Task 1");
+
+        let mut open = task_json("2", "notStarted");
+        open["importance"] = "high".into();
+        open["dueDateTime"] = json!({
+            "dateTime": "2024-05-01T00:00:00.0000000",
+            "timeZone": "UTC",
+        });
+        let open: TodoTask = serde_json::from_value(open).unwrap();
+        assert_eq!(open.to_string(), "[ ] Task 2 (due 2024-05-01, high)");
+    }
+
+    #[test]
+    fn collection_of_tasks() {
+        let page: GraphCollection<TodoTask> = serde_json::from_value(json!({
+            "@odata.context": "https://graph.microsoft.com/v1.0/$metadata#users('me')/todo/lists('list')/tasks",
+            "value": [task_json("1", "notStarted"), task_json("2", "completed")],
+            "@odata.nextLink": "https://graph.microsoft.com/v1.0/me/todo/lists/list/tasks?$skip=2",
+        }))
+        .unwrap();
+        assert_eq!(page.value.len(), 2);
+        assert_eq!(page.value[1].status, TaskStatus::Completed);
+        assert_eq!(
+            page.next_link.as_deref(),
+            Some("https://graph.microsoft.com/v1.0/me/todo/lists/list/tasks?$skip=2")
+        );
+        assert_eq!(page.delta_link, None);
+        assert_eq!(page.count, None);
+
+        let last: GraphCollection<TodoTask> =
+            serde_json::from_value(json!({ "value": [] })).unwrap();
+        assert!(last.value.is_empty());
+        assert_eq!(last.next_link, None);
+    }
+
+    #[test]
+    fn well_known_list_names() {
+        let names = [
+            ("none", WellKnownListName::None),
+            ("defaultList", WellKnownListName::DefaultList),
+            ("flaggedEmails", WellKnownListName::FlaggedEmails),
+            ("unknownFutureValue", WellKnownListName::Other),
+        ];
+        for (value, name) in names {
+            let mut list = json!({
+                "id": "l1",
+                "displayName": "List",
+                "isOwner": true,
+                "isShared": false,
+            });
+            list["wellknownListName"] = value.into();
+            let list: TodoTaskList = serde_json::from_value(list).unwrap();
+            assert_eq!(list.well_known_list_name, name, "{}", value);
+        }
+        assert!(WellKnownListName::DefaultList.is_default());
+        assert!(!WellKnownListName::Other.is_default());
+        assert!(WellKnownListName::FlaggedEmails.is_flagged_emails());
+        assert!(!WellKnownListName::None.is_flagged_emails());
+    }
+}
@@ -0,0 +1,30 @@
+//! The types most programs need, to be glob imported
+//! with `use mstodo_lib::prelude::*`
+pub use crate::{
+    auth::DeviceCodeAuthentication,
+    client::{BulkResult, TaskQuery, TodoClient},
+    error::Error,
+    models::{Importance, NewTask, TaskPatch, TaskStatus, TodoTask, TodoTaskList, User},
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn models_keep_their_client_paths() {
+        let task: crate::client::models::NewTask = NewTask::new("Pay rent");
+        let task: TodoTask = serde_json::from_value(serde_json::json!({
+            "id": "t1",
+            "title": task.title,
+            "status": "notStarted",
+            "importance": "normal",
+            "isReminderOn": false,
+            "createdDateTime": "2024-05-01T00:00:00Z",
+            "lastModifiedDateTime": "2024-05-01T00:00:00Z",
+        }))
+        .unwrap();
+        assert_eq!(task.status, TaskStatus::NotStarted);
+        assert_eq!(task.importance, Importance::Normal);
+    }
+}
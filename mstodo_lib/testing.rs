@@ -0,0 +1,140 @@
+//! Helpers shared by the tests, pointing clients at a mock server
+//! and keeping tokens in a temporary directory instead of the keyring.
+use std::path::Path;
+
+use serde_json::json;
+use wiremock::MockServer;
+
+use crate::{
+    auth::{
+        responses::AuthenticationResponse, store::TokenStore, DeviceCodeAuthentication,
+        DeviceCodeAuthenticationBuilder, RetryPolicy, TokenProvider,
+    },
+    client::{TodoClient, TodoClientBuilder},
+};
+
+/// Tenant of the tests, any valid tenant id works with the mock server
+pub const TENANT: &str = "00000000-0000-0000-0000-000000000000";
+pub const TOKEN_PATH: &str = "/00000000-0000-0000-0000-000000000000/oauth2/v2.0/token";
+pub const DEVICE_CODE_PATH: &str = "/00000000-0000-0000-0000-000000000000/oauth2/v2.0/devicecode";
+
+/// Held by the tests reading or setting the token environment variables,
+/// as the environment is shared by every test of the process
+pub static ENV_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// File only store in `dir`
+pub fn token_store(dir: &Path) -> TokenStore {
+    TokenStore::default().with_config_dir(dir).without_keyring()
+}
+
+/// HTTP client without timers of its own, which would make
+/// tests with a paused clock jump ahead while waiting for the server
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .pool_idle_timeout(None)
+        .build()
+        .unwrap()
+}
+
+/// Authenticator talking to `server`, never retrying nor jittering
+pub fn auth_builder(server: &MockServer, dir: &Path) -> DeviceCodeAuthenticationBuilder {
+    DeviceCodeAuthentication::builder()
+        .http_client(http_client())
+        .authority_host(&server.uri())
+        .tenant(TENANT)
+        .retry_policy(RetryPolicy::none())
+        .poll_jitter(0.0)
+        .presenter(SilentPresenter)
+        .token_store(token_store(dir))
+}
+
+pub fn authenticator(server: &MockServer, dir: &Path) -> DeviceCodeAuthentication {
+    auth_builder(server, dir).build().unwrap()
+}
+
+/// Client sending both Graph and token requests to `server`
+pub fn client_builder(server: &MockServer) -> TodoClientBuilder {
+    TodoClient::builder().graph_base_url(&server.uri())
+}
+
+pub fn client(server: &MockServer, dir: &Path) -> TodoClient {
+    client_builder(server).build(token_provider(server, dir))
+}
+
+pub fn token_provider(server: &MockServer, dir: &Path) -> TokenProvider {
+    TokenProvider::new(
+        auth_response("access", "refresh"),
+        authenticator(server, dir),
+    )
+}
+
+pub fn auth_response(access_token: &str, refresh_token: &str) -> AuthenticationResponse {
+    serde_json::from_value(token_json(access_token, refresh_token)).unwrap()
+}
+
+/// Body of a successful token response
+pub fn token_json(access_token: &str, refresh_token: &str) -> serde_json::Value {
+    json!({
+        "token_type": "Bearer",
+        "scope": "Tasks.ReadWrite User.Read",
+        "expires_in": 3600,
+        "ext_expires_in": 3600,
+        "access_token": access_token,
+        "refresh_token": refresh_token,
+    })
+}
+
+/// Body of a failed token response
+pub fn oauth_error_json(error: &str) -> serde_json::Value {
+    json!({
+        "error": error,
+        "error_description": format!("AADSTS00000: {}", error),
+        "error_codes": [0],
+        "timestamp": "2024-05-01 00:00:00Z",
+        "trace_id": "trace",
+        "correlation_id": "correlation",
+    })
+}
+
+/// Body of a device code response polled every second
+pub fn device_code_json() -> serde_json::Value {
+    json!({
+        "device_code": "device-code",
+        "user_code": "ABCD-EFGH",
+        "verification_uri": "https://microsoft.com/devicelogin",
+        "expires_in": 900,
+        "interval": 1,
+        "message": "Enter ABCD-EFGH at https://microsoft.com/devicelogin",
+    })
+}
+
+/// Minimal task as returned by Graph
+pub fn task_json(id: &str, title: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "title": title,
+        "status": "notStarted",
+        "importance": "normal",
+        "isReminderOn": false,
+        "createdDateTime": "2024-05-01T00:00:00Z",
+        "lastModifiedDateTime": "2024-05-01T00:00:00Z",
+        "body": { "content": "", "contentType": "text" },
+    })
+}
+
+/// Minimal task list as returned by Graph
+pub fn list_json(id: &str, display_name: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "displayName": display_name,
+        "isOwner": true,
+        "isShared": false,
+        "wellknownListName": "none",
+    })
+}
+
+struct SilentPresenter;
+
+impl crate::auth::DeviceCodePresenter for SilentPresenter {
+    fn present(&self, _user_code: &str, _verification_uri: &str, _message: &str) {}
+}
@@ -1,4 +1,9 @@
-use serde::{Deserialize, Deserializer};
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Deserializer, Serializer};
 
 pub fn str2u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
@@ -8,3 +13,201 @@ where
         .parse::<u64>()
         .map_err(serde::de::Error::custom)
 }
+
+/// Reverse of `str2u64`, writes a `u64` as a JSON string
+pub fn u64_to_str<S>(value: &u64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&value.to_string())
+}
+
+/// Like `str2u64` but for fields that may be missing or null
+pub fn option_str2u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse::<u64>().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Parses Graph timestamps such as `2024-01-02T03:04:05.123Z`,
+/// with or without fractional seconds or the trailing `Z`
+#[cfg(feature = "chrono")]
+pub fn deserialize_graph_datetime<'de, D>(
+    deserializer: D,
+) -> Result<chrono::DateTime<chrono::Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    if let Ok(date_time) = chrono::DateTime::parse_from_rfc3339(&value) {
+        return Ok(date_time.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M:%S%.f")
+        .map(|naive| naive.and_utc())
+        .map_err(serde::de::Error::custom)
+}
+
+/// Writes a timestamp in the format Graph returns them
+#[cfg(feature = "chrono")]
+pub fn serialize_graph_datetime<S>(
+    value: &chrono::DateTime<chrono::Utc>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&value.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true))
+}
+
+/// Directory holding mstodo's files, `$XDG_CONFIG_HOME/mstodo` when set
+/// and `~/.config/mstodo` otherwise
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+            PathBuf::from(home).join(".config")
+        }
+    };
+    Some(base.join("mstodo"))
+}
+
+/// `User-Agent` sent with every request, as asked by Graph to diagnose throttling
+pub const USER_AGENT: &str = concat!("mstodo/", env!("CARGO_PKG_VERSION"));
+
+/// Headers sent with every request unless overridden, i.e. [USER_AGENT]
+pub fn default_headers() -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::USER_AGENT,
+        reqwest::header::HeaderValue::from_static(USER_AGENT),
+    );
+    headers
+}
+
+/// Delay requested by the server through the `Retry-After` header,
+/// given either in seconds or as an HTTP date
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Formats a point in time as `YYYY-MM-DDTHH:MM:SS` in UTC, as expected by Graph
+pub fn format_utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // civil date from days since epoch, see "http://howardhinnant.github.io/date_algorithms.html"
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Sizes {
+        #[serde(deserialize_with = "str2u64", serialize_with = "u64_to_str")]
+        size: u64,
+        #[serde(default, deserialize_with = "option_str2u64")]
+        limit: Option<u64>,
+    }
+
+    #[test]
+    fn u64_round_trips_as_a_string() {
+        let sizes = Sizes {
+            size: u64::MAX,
+            limit: None,
+        };
+        let value = serde_json::to_value(&sizes).unwrap();
+        assert_eq!(value["size"], json!("18446744073709551615"));
+        assert_eq!(serde_json::from_value::<Sizes>(value).unwrap(), sizes);
+    }
+
+    #[test]
+    fn optional_u64_from_a_string() {
+        let parse = |value| serde_json::from_value::<Sizes>(value).map(|sizes| sizes.limit);
+        assert_eq!(
+            parse(json!({ "size": "1", "limit": "42" })).unwrap(),
+            Some(42)
+        );
+        assert_eq!(parse(json!({ "size": "1", "limit": null })).unwrap(), None);
+        assert_eq!(parse(json!({ "size": "1" })).unwrap(), None);
+        assert!(parse(json!({ "size": "1", "limit": "many" })).is_err());
+        assert!(parse(json!({ "size": "-1" })).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Timestamp {
+        #[serde(
+            deserialize_with = "deserialize_graph_datetime",
+            serialize_with = "serialize_graph_datetime"
+        )]
+        at: chrono::DateTime<chrono::Utc>,
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn graph_datetime_shapes() {
+        use chrono::TimeZone;
+
+        let parse = |at: &str| serde_json::from_value::<Timestamp>(json!({ "at": at }));
+        let whole = chrono::Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let fractional = whole + chrono::Duration::nanoseconds(123_456_700);
+        for (at, expected) in [
+            ("2024-01-02T03:04:05.1234567Z", fractional),
+            ("2024-01-02T03:04:05.1234567", fractional),
+            ("2024-01-02T03:04:05Z", whole),
+            ("2024-01-02T03:04:05", whole),
+            ("2024-01-02T05:04:05+02:00", whole),
+        ] {
+            assert_eq!(parse(at).unwrap().at, expected, "{}", at);
+        }
+        assert!(parse("2024-01-02").is_err());
+        assert!(parse("yesterday").is_err());
+
+        let timestamp = Timestamp { at: fractional };
+        let value = serde_json::to_value(&timestamp).unwrap();
+        assert_eq!(value["at"], json!("2024-01-02T03:04:05.123456700Z"));
+        assert_eq!(
+            serde_json::from_value::<Timestamp>(value).unwrap(),
+            timestamp
+        );
+    }
+}
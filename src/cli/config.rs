@@ -0,0 +1,90 @@
+//! Defaults read from `config.toml` in [mstodo_lib::utils::config_dir],
+//! command line flags taking precedence over them
+use std::path::Path;
+
+use mstodo_lib::error::Error;
+use serde::Deserialize;
+
+use super::output::OutputFormat;
+
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    /// Id or name of the list `default` stands for, instead of the built-in "Tasks" list
+    pub default_list: Option<String>,
+    /// Same as `--output`
+    pub output: Option<OutputFormat>,
+    /// Same as `--profile`
+    pub profile: Option<String>,
+    /// Root of the Graph API, e.g. `https://graph.microsoft.us` for a national cloud
+    pub graph_base_url: Option<String>,
+}
+
+impl Config {
+    /// Reads the config file, an empty config being used when there is none
+    pub fn load() -> Result<Self, Error> {
+        match mstodo_lib::utils::config_dir() {
+            Some(dir) => Self::load_from(&dir.join(CONFIG_FILE)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)
+                .map_err(|e| Error::InvalidInput(format!("{}: {}", path.display(), e))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_every_key() {
+        let config = Config::parse(
+            r#"
+            default_list = "Groceries"
+            output = "json"
+            profile = "work"
+            graph_base_url = "https://graph.microsoft.us"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                default_list: Some("Groceries".to_owned()),
+                output: Some(OutputFormat::Json),
+                profile: Some("work".to_owned()),
+                graph_base_url: Some("https://graph.microsoft.us".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn parse_rejects_an_unknown_output() {
+        assert!(Config::parse(r#"output = "yaml""#).is_err());
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::load_from(&dir.path().join(CONFIG_FILE)).unwrap();
+        assert_eq!(config, Config::default());
+    }
+}
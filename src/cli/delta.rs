@@ -0,0 +1,139 @@
+//! Delta links saved by `tasks --delta` between runs, one file per profile and list
+//! under `delta/` in [mstodo_lib::utils::config_dir]
+use std::path::{Path, PathBuf};
+
+use mstodo_lib::{error::Error, models::DeltaPage, prelude::TodoClient};
+
+/// Directory the delta links are saved in
+pub fn dir() -> Option<PathBuf> {
+    Some(mstodo_lib::utils::config_dir()?.join("delta"))
+}
+
+/// File holding the delta link of a list. List ids are hex encoded
+/// as they may contain characters that are not allowed in file names.
+fn path(dir: &Path, profile: Option<&str>, list_id: &str) -> PathBuf {
+    let list: String = list_id.bytes().map(|b| format!("{:02x}", b)).collect();
+    dir.join(format!("{}-{}", profile.unwrap_or("default"), list))
+}
+
+/// Delta link saved by the previous run, [None] meaning a full sync is needed
+pub fn load(dir: &Path, profile: Option<&str>, list_id: &str) -> Option<String> {
+    let link = std::fs::read_to_string(path(dir, profile, list_id)).ok()?;
+    Some(link.trim().to_owned()).filter(|link| !link.is_empty())
+}
+
+pub fn save(
+    dir: &Path,
+    profile: Option<&str>,
+    list_id: &str,
+    delta_link: &str,
+) -> Result<(), Error> {
+    std::fs::create_dir_all(dir)?;
+    Ok(std::fs::write(path(dir, profile, list_id), delta_link)?)
+}
+
+/// Tasks of a list changed since the previous sync, every task the first time
+/// or when the saved link expired. The link to the next changes is saved.
+pub async fn sync(
+    client: &TodoClient,
+    dir: &Path,
+    profile: Option<&str>,
+    list_id: &str,
+) -> Result<DeltaPage, Error> {
+    let delta_link = load(dir, profile, list_id);
+    let page = match client
+        .list_tasks_delta(list_id, delta_link.as_deref())
+        .await
+    {
+        // the saved link expired, starting over
+        Err(Error::Api(err)) if err.status == 410 && delta_link.is_some() => {
+            client.list_tasks_delta(list_id, None).await?
+        }
+        page => page?,
+    };
+    if let Some(delta_link) = &page.delta_link {
+        save(dir, profile, list_id, delta_link)?;
+    }
+    Ok(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::cli::{testing, StatusFilter};
+
+    const DELTA_PATH: &str = "/v1.0/me/todo/lists/list/tasks/delta";
+
+    #[tokio::test]
+    async fn second_run_only_gets_the_changes() {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let next = format!("{}{}?$deltatoken=first", server.uri(), DELTA_PATH);
+        Mock::given(method("GET"))
+            .and(path(DELTA_PATH))
+            .and(query_param("$deltatoken", "first"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    testing::task_json("3", "New", "notStarted"),
+                    { "id": "2", "@removed": { "reason": "deleted" } },
+                ],
+                "@odata.deltaLink": format!("{}{}?$deltatoken=second", server.uri(), DELTA_PATH),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(DELTA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "value": [
+                    testing::task_json("1", "Done", "completed"),
+                    testing::task_json("2", "Open", "notStarted"),
+                ],
+                "@odata.deltaLink": next,
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = testing::client(&server);
+
+        let first = sync(&client, dir.path(), None, "list").await.unwrap();
+        let status = StatusFilter::default_for(true);
+        let printed = first
+            .tasks
+            .iter()
+            .filter(|task| status.matches(&task.status))
+            .count();
+        assert_eq!(printed, 2, "completed tasks are printed with --delta");
+        assert_eq!(load(dir.path(), None, "list"), Some(next));
+
+        let second = sync(&client, dir.path(), None, "list").await.unwrap();
+        assert_eq!(second.tasks.len(), 1);
+        assert_eq!(second.tasks[0].id, "3");
+        assert_eq!(second.removed, vec!["2".to_owned()]);
+        assert!(load(dir.path(), None, "list")
+            .unwrap()
+            .ends_with("$deltatoken=second"));
+    }
+
+    #[test]
+    fn links_are_kept_per_profile_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        save(dir.path(), None, "a/b", "default-link").unwrap();
+        save(dir.path(), Some("work"), "a/b", "work-link").unwrap();
+        assert_eq!(
+            load(dir.path(), None, "a/b").as_deref(),
+            Some("default-link")
+        );
+        assert_eq!(
+            load(dir.path(), Some("work"), "a/b").as_deref(),
+            Some("work-link")
+        );
+        assert_eq!(load(dir.path(), None, "other"), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Reading tasks to import from JSON or CSV files
+use std::path::Path;
+
+use mstodo_lib::{
+    error::Error,
+    models::{Importance, NewTask},
+};
+
+/// Row of a CSV file, the header naming the columns
+#[derive(serde::Deserialize, Debug)]
+struct CsvTask {
+    title: String,
+    #[serde(default)]
+    due: String,
+    #[serde(default)]
+    importance: String,
+    #[serde(default)]
+    note: String,
+}
+
+/// Reads a `.csv` file with `title,due,importance,note` columns,
+/// or any other file as a JSON array of [NewTask]
+pub fn read_tasks(path: &Path) -> Result<Vec<NewTask>, Error> {
+    let content = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => parse_csv(&content),
+        _ => parse_json(&content),
+    }
+}
+
+fn parse_json(content: &str) -> Result<Vec<NewTask>, Error> {
+    let new_tasks: Vec<NewTask> = serde_json::from_str(content)?;
+    for (i, new_task) in new_tasks.iter().enumerate() {
+        if let Importance::Unknown(importance) = &new_task.importance {
+            return Err(Error::InvalidInput(format!(
+                "task {}: {}",
+                i + 1,
+                unknown_importance(importance)
+            )));
+        }
+    }
+    Ok(new_tasks)
+}
+
+fn parse_csv(content: &str) -> Result<Vec<NewTask>, Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let mut new_tasks = Vec::new();
+    for (i, row) in reader.deserialize::<CsvTask>().enumerate() {
+        // line 1 is the header
+        let row = row.map_err(|e| Error::InvalidInput(format!("line {}: {}", i + 2, e)))?;
+        let mut new_task = NewTask::new(&row.title);
+        if !row.due.is_empty() {
+            let due = super::parse_due(&row.due)
+                .map_err(|e| Error::InvalidInput(format!("line {}: {}", i + 2, e)))?;
+            new_task = new_task.due(&due);
+        }
+        if !row.importance.is_empty() {
+            let importance = match Importance::from(row.importance.to_lowercase()) {
+                Importance::Unknown(_) => {
+                    return Err(Error::InvalidInput(format!(
+                        "line {}: {}",
+                        i + 2,
+                        unknown_importance(&row.importance)
+                    )))
+                }
+                importance => importance,
+            };
+            new_task = new_task.importance(importance);
+        }
+        if !row.note.is_empty() {
+            new_task = new_task.body(row.note.as_str());
+        }
+        new_tasks.push(new_task);
+    }
+    Ok(new_tasks)
+}
+
+fn unknown_importance(importance: &str) -> String {
+    format!(
+        "unknown importance `{}`, expected low, normal or high",
+        importance
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_rows() {
+        let new_tasks = parse_csv(
+            "title,due,importance,note\n\
+             Buy milk,2024-05-01,High,semi-skimmed\n\
+             Call mum,,,\n",
+        )
+        .unwrap();
+        assert_eq!(new_tasks.len(), 2);
+        assert_eq!(new_tasks[0].title, "Buy milk");
+        assert_eq!(new_tasks[0].importance, Importance::High);
+        assert_eq!(
+            new_tasks[0].due_date_time.as_ref().unwrap().date_time,
+            "2024-05-01T00:00:00"
+        );
+        assert_eq!(new_tasks[0].body.as_ref().unwrap().content, "semi-skimmed");
+        assert_eq!(new_tasks[1], NewTask::new("Call mum"));
+    }
+
+    #[test]
+    fn csv_rejects_an_unknown_importance() {
+        let err = parse_csv("title,importance\nBuy milk,normal\nPay rent,urgent\n").unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidInput(message) if message.starts_with("line 3: unknown importance `urgent`")),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn csv_rejects_an_invalid_due_date() {
+        let err = parse_csv("title,due\nPay rent,2024-02-31\n").unwrap_err();
+        assert!(matches!(&err, Error::InvalidInput(message) if message.starts_with("line 2:")));
+    }
+
+    #[test]
+    fn json_tasks() {
+        let new_tasks = parse_json(
+            r#"[
+                { "title": "Buy milk", "importance": "high" },
+                { "title": "Call mum" }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(new_tasks.len(), 2);
+        assert_eq!(new_tasks[0].importance, Importance::High);
+        assert_eq!(new_tasks[1], NewTask::new("Call mum"));
+    }
+
+    #[test]
+    fn json_rejects_an_unknown_importance() {
+        let err = parse_json(r#"[{ "title": "Pay rent", "importance": "urgent" }]"#).unwrap_err();
+        assert!(matches!(&err, Error::InvalidInput(message) if message.starts_with("task 1:")));
+    }
+
+    #[test]
+    fn file_extension_selects_the_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv = dir.path().join("tasks.CSV");
+        std::fs::write(&csv, "title\nBuy milk\n").unwrap();
+        assert_eq!(read_tasks(&csv).unwrap(), vec![NewTask::new("Buy milk")]);
+        let json = dir.path().join("tasks.json");
+        std::fs::write(&json, r#"[{ "title": "Buy milk" }]"#).unwrap();
+        assert_eq!(read_tasks(&json).unwrap(), vec![NewTask::new("Buy milk")]);
+    }
+}
@@ -0,0 +1,871 @@
+//! Command line interface of the `mstodo` binary
+mod config;
+mod delta;
+mod import;
+mod output;
+#[cfg(test)]
+mod testing;
+
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+pub use config::Config;
+use futures::StreamExt;
+use mstodo_lib::{
+    auth::{responses::IdTokenClaims, TokenProvider},
+    error::AuthenticationError,
+    prelude::*,
+};
+use output::OutputFormat;
+use tokio_util::sync::CancellationToken;
+
+/// Manage Microsoft To Do from the command line
+#[derive(Parser, Debug)]
+#[command(name = "mstodo", version, about)]
+pub struct Cli {
+    /// How `lists` and `tasks` print their results, `table` unless set in the config file
+    #[arg(long, global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+    /// Account to use, each one having its own stored token
+    #[arg(long, global = true, env = "MSTODO_PROFILE")]
+    pub profile: Option<String>,
+    /// Print the changes `add`, `done`, `rm` and `import` would make instead of making them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// Never style the output, same as setting `NO_COLOR`
+    #[arg(long, global = true)]
+    pub no_color: bool,
+    /// Log requests, including the request and correlation ids of failures
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+    #[command(subcommand)]
+    pub command: Command,
+    /// Defaults for what the flags leave unset
+    #[arg(skip)]
+    pub config: Config,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Sign in with a device code and store the token
+    Login,
+    /// Remove the stored token
+    Logout,
+    /// Print the profiles with a stored token
+    Accounts,
+    /// Print the signed-in user
+    Whoami,
+    /// Check that Graph can be reached with the stored token and print the latency
+    Ping,
+    /// Print every task list
+    Lists,
+    /// Print the tasks of a list
+    Tasks {
+        /// Id or name of the list, or `default`
+        #[arg(default_value = "default")]
+        list: String,
+        /// Which tasks to print, `open` unless `--delta` is set
+        #[arg(long, value_enum)]
+        status: Option<StatusFilter>,
+        /// Only print the tasks changed since the previous `--delta` run of this list,
+        /// every task the first time
+        #[arg(long)]
+        delta: bool,
+    },
+    /// Print the tasks of every list whose title or note contains a keyword
+    Search {
+        /// Text to look for, ignoring case
+        query: String,
+    },
+    /// Create a task and print its id
+    Add {
+        /// Id or name of the list, or `default`
+        list: String,
+        /// Title of the task
+        title: String,
+        /// Due date, `today`, `tomorrow` or e.g. `2024-05-01`
+        #[arg(long, value_parser = parse_due)]
+        due: Option<String>,
+        /// Mark the task as important
+        #[arg(long)]
+        important: bool,
+        /// Text of the task body
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Mark tasks as completed
+    Done {
+        /// Id or name of the list, or `default`
+        list: String,
+        /// Ids or titles of the tasks
+        #[arg(required = true)]
+        tasks: Vec<String>,
+    },
+    /// Delete a task
+    Rm {
+        /// Id or name of the list, or `default`
+        list: String,
+        /// Id or title of the task
+        task: String,
+    },
+    /// Create the tasks read from a JSON or CSV file
+    Import {
+        /// Id or name of the list, or `default`
+        list: String,
+        /// JSON array of tasks, or CSV with `title,due,importance,note` columns
+        file: PathBuf,
+    },
+    /// Print a completion script for a shell
+    #[command(hide = true)]
+    Completions { shell: clap_complete::Shell },
+}
+
+/// Value of the `--status` flag of `tasks`
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatusFilter {
+    All,
+    /// Every task not completed
+    #[default]
+    Open,
+    Completed,
+    Deferred,
+    /// Waiting on others
+    Waiting,
+}
+
+impl StatusFilter {
+    /// Filter used without `--status`, every change being printed with `--delta`
+    pub fn default_for(delta: bool) -> Self {
+        if delta {
+            Self::All
+        } else {
+            Self::default()
+        }
+    }
+
+    /// `$filter` expression selecting the tasks
+    pub fn filter(self) -> Option<&'static str> {
+        match self {
+            Self::All => None,
+            Self::Open => Some("status ne 'completed'"),
+            Self::Completed => Some("status eq 'completed'"),
+            Self::Deferred => Some("status eq 'deferred'"),
+            Self::Waiting => Some("status eq 'waitingOnOthers'"),
+        }
+    }
+
+    /// Same as [Self::filter] for tasks already fetched
+    pub fn matches(self, status: &TaskStatus) -> bool {
+        match self {
+            Self::All => true,
+            Self::Open => *status != TaskStatus::Completed,
+            Self::Completed => *status == TaskStatus::Completed,
+            Self::Deferred => *status == TaskStatus::Deferred,
+            Self::Waiting => *status == TaskStatus::WaitingOnOthers,
+        }
+    }
+}
+
+/// The command tree shared by the parser and the completion scripts
+pub fn command() -> clap::Command {
+    Cli::command()
+}
+
+impl Cli {
+    /// Fills the flags left unset from `config`
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.output = self.output.or(config.output);
+        self.profile = self.profile.or_else(|| config.profile.clone());
+        self.config = config;
+        self
+    }
+
+    fn authenticator(&self) -> Result<DeviceCodeAuthentication, Error> {
+        match &self.profile {
+            Some(profile) => DeviceCodeAuthentication::for_profile(profile),
+            None => Ok(DeviceCodeAuthentication::new()),
+        }
+    }
+
+    async fn client(&self) -> Result<TodoClient, Error> {
+        let tokens = TokenProvider::login(self.authenticator()?).await?;
+        let mut builder = TodoClient::builder().dry_run(self.dry_run);
+        if let Some(graph_base_url) = &self.config.graph_base_url {
+            builder = builder.graph_base_url(graph_base_url);
+        }
+        Ok(builder.build(tokens))
+    }
+
+    /// Same as [TodoClient::resolve_list], `default` standing for
+    /// the configured default list when there is one
+    async fn resolve_list(&self, client: &TodoClient, list: &str) -> Result<TodoTaskList, Error> {
+        match &self.config.default_list {
+            Some(default_list) if list == "default" => client.resolve_list(default_list).await,
+            _ => client.resolve_list(list).await,
+        }
+    }
+
+    pub async fn run(self) -> Result<(), Error> {
+        let output = self.output.unwrap_or_default();
+        let renderer = output.renderer(output::use_color(self.no_color));
+        match &self.command {
+            Command::Import { list, file } => {
+                let new_tasks = import::read_tasks(file)?;
+                let client = self.client().await?;
+                let list = self.resolve_list(&client, list).await?;
+                import(&client, &list.id, new_tasks).await?;
+            }
+            Command::Completions { shell } => {
+                clap_complete::generate(*shell, &mut command(), "mstodo", &mut std::io::stdout());
+            }
+            Command::Login => {
+                let cancel = CancellationToken::new();
+                let interrupt = tokio::spawn(cancel_on_ctrl_c(cancel.clone()));
+                let auth = self
+                    .authenticator()?
+                    .authenticate_with_cancellation(&cancel)
+                    .await;
+                interrupt.abort();
+                let auth = auth?;
+                match auth.decode_id_token() {
+                    Ok(Some(IdTokenClaims {
+                        preferred_username: Some(user),
+                        ..
+                    })) => println!("Signed in as {}", user),
+                    _ => println!("Logged in"),
+                }
+            }
+            Command::Logout => {
+                self.authenticator()?.logout()?;
+                println!("Logged out");
+            }
+            Command::Accounts => {
+                for profile in self.authenticator()?.profiles() {
+                    println!("{}", profile);
+                }
+            }
+            Command::Whoami => {
+                let client = self.client().await?;
+                println!("{}", renderer.render_user(&client.me().await?)?);
+            }
+            Command::Ping => {
+                let client = self.client().await?;
+                let latency = client.ping().await?;
+                println!("Graph answered in {} ms", latency.as_millis());
+            }
+            Command::Lists => {
+                let client = self.client().await?;
+                let lists = client.list_task_lists().await?;
+                println!("{}", renderer.render_lists(&lists)?);
+            }
+            Command::Tasks {
+                list,
+                status,
+                delta: true,
+            } => {
+                let client = self.client().await?;
+                let list = self.resolve_list(&client, list).await?;
+                let dir = delta::dir().ok_or_else(|| {
+                    Error::InvalidInput("no config directory to save the delta link in".to_owned())
+                })?;
+                let page = delta::sync(&client, &dir, self.profile.as_deref(), &list.id).await?;
+                let status = status.unwrap_or(StatusFilter::default_for(true));
+                let tasks: Vec<TodoTask> = page
+                    .tasks
+                    .into_iter()
+                    .filter(|task| status.matches(&task.status))
+                    .collect();
+                println!("{}", renderer.render_tasks(&tasks)?);
+                for id in &page.removed {
+                    eprintln!("Removed {}", id);
+                }
+            }
+            Command::Tasks {
+                list,
+                status,
+                delta: false,
+            } => {
+                let client = self.client().await?;
+                let list = self.resolve_list(&client, list).await?;
+                let status = status.unwrap_or(StatusFilter::default_for(false));
+                let query = match status.filter() {
+                    Some(filter) => TaskQuery::new().filter(filter),
+                    None => TaskQuery::new(),
+                };
+                if output == OutputFormat::Ndjson {
+                    let mut tasks =
+                        std::pin::pin!(client.stream_tasks_with_query(&list.id, &query));
+                    while let Some(task) = tasks.next().await {
+                        println!("{}", output::NdjsonRenderer::render_task(&task?)?);
+                    }
+                    return Ok(());
+                }
+                let tasks = client.list_tasks_with_query(&list.id, &query).await?;
+                println!("{}", renderer.render_tasks(&tasks)?);
+            }
+            Command::Search { query } => {
+                let client = self.client().await?;
+                let hits = client.search_tasks(query).await?;
+                println!("{}", renderer.render_search(&hits)?);
+            }
+            Command::Add {
+                list,
+                title,
+                due,
+                important,
+                note,
+            } => {
+                let client = self.client().await?;
+                let list = self.resolve_list(&client, list).await?;
+                let mut new_task = NewTask::new(title);
+                if let Some(due) = due {
+                    new_task = new_task.due(due);
+                }
+                if *important {
+                    new_task = new_task.importance(Importance::High);
+                }
+                if let Some(note) = note {
+                    new_task = new_task.body(note.as_str());
+                }
+                if let Some(task) = dry_run(client.create_task(&list.id, new_task).await)? {
+                    println!("{}", task.id);
+                }
+            }
+            Command::Done { list, tasks } => {
+                let client = self.client().await?;
+                let list = self.resolve_list(&client, list).await?;
+                complete(&client, &list.id, tasks).await?;
+            }
+            Command::Rm { list, task } => {
+                let client = self.client().await?;
+                let list = self.resolve_list(&client, list).await?;
+                let task = resolve_task(&client, &list.id, task).await?;
+                if dry_run(client.delete_task(&list.id, &task.id).await)?.is_some() {
+                    println!("Deleted {}", task.title);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints why a command failed and returns the exit status,
+/// 130 being the convention for an interrupted program
+pub fn report(err: &Error) -> i32 {
+    if let Some(api_error) = err.api_error() {
+        tracing::error!(
+            status = api_error.status,
+            code = api_error.code,
+            request_id = api_error.request_id,
+            date = api_error.date,
+            "request failed"
+        );
+    }
+    match err {
+        Error::Auth(AuthenticationError::Cancelled) => {
+            eprintln!("Login cancelled.");
+            130
+        }
+        err => {
+            eprintln!("error: {}", err);
+            1
+        }
+    }
+}
+
+/// Cancels `cancel` once the user hits Ctrl-C, instead of the process being killed
+async fn cancel_on_ctrl_c(cancel: CancellationToken) {
+    if tokio::signal::ctrl_c().await.is_ok() {
+        cancel.cancel();
+    }
+}
+
+/// Prints the request a dry run did not send, [None] standing for it
+fn dry_run<T>(result: Result<T, Error>) -> Result<Option<T>, Error> {
+    match result {
+        Err(Error::DryRun(request)) => {
+            println!("Would send {}", request);
+            Ok(None)
+        }
+        result => result.map(Some),
+    }
+}
+
+/// Creates `new_tasks` one after the other, printing why those that failed did.
+/// Fails with [Error::TasksFailed] when any did, short of a dry run.
+async fn import(client: &TodoClient, list_id: &str, new_tasks: Vec<NewTask>) -> Result<(), Error> {
+    let results = client.import_tasks(list_id, new_tasks).await;
+    let total = results.len();
+    let (mut imported, mut failed) = (0, 0);
+    for (i, result) in results.into_iter().enumerate() {
+        match dry_run(result) {
+            Ok(Some(_)) => imported += 1,
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("task {}: {}", i + 1, err);
+                failed += 1;
+            }
+        }
+    }
+    println!("Imported {} tasks, {} failed", imported, failed);
+    if failed > 0 {
+        return Err(Error::TasksFailed { failed, total });
+    }
+    Ok(())
+}
+
+/// Completes the tasks of a list whose id or title is in `tasks`,
+/// printing why those that failed did.
+/// Fails with [Error::TasksFailed] when any did, short of a dry run.
+async fn complete(client: &TodoClient, list_id: &str, tasks: &[String]) -> Result<(), Error> {
+    let all_tasks = client.list_tasks(list_id).await?;
+    let tasks = tasks
+        .iter()
+        .map(|task| find_task(&all_tasks, task))
+        .collect::<Result<Vec<_>, _>>()?;
+    let task_ids: Vec<&str> = tasks.iter().map(|task| task.id.as_str()).collect();
+    let result = client.complete_tasks(list_id, &task_ids).await?;
+    let title = |id: &str| {
+        tasks
+            .iter()
+            .find(|task| task.id == id)
+            .map_or(id.to_owned(), |task| task.title.clone())
+    };
+    for task_id in &result.succeeded {
+        println!("Completed {}", title(task_id));
+    }
+    let mut failed = 0;
+    for (task_id, err) in result.failed {
+        if let Err(err) = dry_run(Err::<(), _>(err)) {
+            eprintln!("{}: {}", title(&task_id), err);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(Error::TasksFailed {
+            failed,
+            total: tasks.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Finds the task of a list whose id or title is `id_or_title`.
+/// Fails when several tasks share that title.
+async fn resolve_task(
+    client: &TodoClient,
+    list_id: &str,
+    id_or_title: &str,
+) -> Result<TodoTask, Error> {
+    find_task(&client.list_tasks(list_id).await?, id_or_title)
+}
+
+/// Same as [resolve_task] among already listed tasks
+fn find_task(tasks: &[TodoTask], id_or_title: &str) -> Result<TodoTask, Error> {
+    if let Some(task) = tasks.iter().find(|task| task.id == id_or_title) {
+        return Ok(task.clone());
+    }
+    let mut matches: Vec<TodoTask> = tasks
+        .iter()
+        .filter(|task| task.title == id_or_title)
+        .cloned()
+        .collect();
+    match matches.len() {
+        0 => Err(Error::NoMatch(format!("no task titled {}", id_or_title))),
+        1 => Ok(matches.remove(0)),
+        _ => Err(Error::InvalidInput(format!(
+            "{} tasks are titled {}, use one of their ids instead: {}",
+            matches.len(),
+            id_or_title,
+            matches
+                .iter()
+                .map(|task| task.id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// Turns a `--due` argument into the start of that day in UTC,
+/// e.g. `2024-05-01` into `2024-05-01T00:00:00`
+fn parse_due(value: &str) -> Result<String, String> {
+    let now = SystemTime::now();
+    let date = match value {
+        "today" => mstodo_lib::utils::format_utc(now)[..10].to_owned(),
+        "tomorrow" => {
+            mstodo_lib::utils::format_utc(now + Duration::from_secs(24 * 60 * 60))[..10].to_owned()
+        }
+        _ => value.to_owned(),
+    };
+    let parts: Vec<&str> = date.split('-').collect();
+    let valid = match parts.as_slice() {
+        [year, month, day] if year.len() == 4 && month.len() == 2 && day.len() == 2 => {
+            match (year.parse(), month.parse(), day.parse::<u32>()) {
+                (Ok(year), Ok(month @ 1..=12), Ok(day)) => {
+                    (1..=days_in_month(year, month)).contains(&day)
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(format!(
+            "expected `today`, `tomorrow` or a date like 2024-05-01, got `{}`",
+            value
+        ));
+    }
+    Ok(format!("{}T00:00:00", date))
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::testing;
+
+    #[test]
+    fn subcommands() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(std::iter::once("mstodo").chain(args.iter().copied()))
+                .unwrap()
+                .command
+        };
+        assert!(matches!(parse(&["login"]), Command::Login));
+        assert!(matches!(parse(&["logout"]), Command::Logout));
+        assert!(matches!(parse(&["lists"]), Command::Lists));
+        assert!(matches!(
+            parse(&["tasks", "Groceries"]),
+            Command::Tasks { list, .. } if list == "Groceries"
+        ));
+    }
+
+    #[test]
+    fn help_is_reported_as_such() {
+        let err = Cli::try_parse_from(["mstodo", "--help"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::DisplayHelp);
+        let err = Cli::try_parse_from(["mstodo"]).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            clap::error::ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        );
+    }
+
+    #[test]
+    fn parse_due_dates() {
+        assert_eq!(parse_due("2024-05-01").unwrap(), "2024-05-01T00:00:00");
+        assert_eq!(parse_due("2024-02-29").unwrap(), "2024-02-29T00:00:00");
+        assert_eq!(parse_due("2000-02-29").unwrap(), "2000-02-29T00:00:00");
+        assert!(parse_due("today").unwrap().ends_with("T00:00:00"));
+        assert!(parse_due("tomorrow").unwrap().ends_with("T00:00:00"));
+    }
+
+    #[test]
+    fn parse_due_rejects_invalid_dates() {
+        for value in [
+            "2024-02-31",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-13-01",
+            "2024-00-10",
+            "2024-05-00",
+            "2024-5-1",
+            "01-05-2024",
+            "next week",
+        ] {
+            assert!(parse_due(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn add_arguments() {
+        let cli = Cli::try_parse_from([
+            "mstodo",
+            "add",
+            "Groceries",
+            "Buy milk",
+            "--due",
+            "2024-05-01",
+            "--important",
+            "--note",
+            "semi-skimmed",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Add {
+                list,
+                title,
+                due,
+                important,
+                note,
+            } => {
+                assert_eq!(list, "Groceries");
+                assert_eq!(title, "Buy milk");
+                assert_eq!(due.as_deref(), Some("2024-05-01T00:00:00"));
+                assert!(important);
+                assert_eq!(note.as_deref(), Some("semi-skimmed"));
+            }
+            command => panic!("unexpected {:?}", command),
+        }
+    }
+
+    #[test]
+    fn add_rejects_an_invalid_due_date() {
+        let err = Cli::try_parse_from(["mstodo", "add", "default", "Pay", "--due", "2024-02-31"])
+            .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn done_and_rm_arguments() {
+        let cli = Cli::try_parse_from(["mstodo", "done", "Groceries", "Buy milk", "t2"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Done { list, tasks } if list == "Groceries" && tasks == ["Buy milk", "t2"]
+        ));
+        let cli = Cli::try_parse_from(["mstodo", "rm", "Groceries", "Buy milk"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Rm { list, task } if list == "Groceries" && task == "Buy milk"
+        ));
+        let err = Cli::try_parse_from(["mstodo", "done", "Groceries"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn find_task_by_id_or_title() {
+        let tasks: Vec<TodoTask> = serde_json::from_value(serde_json::json!([
+            testing::task_json("t1", "Buy milk", "notStarted"),
+            testing::task_json("t2", "Buy milk", "notStarted"),
+            testing::task_json("t3", "Pay rent", "notStarted"),
+        ]))
+        .unwrap();
+        assert_eq!(find_task(&tasks, "t2").unwrap().id, "t2");
+        assert_eq!(find_task(&tasks, "Pay rent").unwrap().id, "t3");
+        let err = find_task(&tasks, "Buy milk").unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidInput(message) if message.contains("t1, t2")),
+            "{:?}",
+            err
+        );
+        let err = find_task(&tasks, "Call mum").unwrap_err();
+        assert!(matches!(err, Error::NoMatch(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn bash_completions_mention_the_subcommands() {
+        let mut script = Vec::new();
+        clap_complete::generate(
+            clap_complete::Shell::Bash,
+            &mut command(),
+            "mstodo",
+            &mut script,
+        );
+        let script = String::from_utf8(script).unwrap();
+        assert!(!script.is_empty());
+        for subcommand in ["login", "logout", "lists", "tasks", "add", "done", "rm"] {
+            assert!(script.contains(subcommand), "{} missing", subcommand);
+        }
+    }
+
+    #[test]
+    fn status_values_map_to_filters() {
+        let filter = |args: &[&str]| {
+            let args = ["mstodo", "tasks"].iter().chain(args).copied();
+            match Cli::try_parse_from(args).unwrap().command {
+                Command::Tasks { status, delta, .. } => {
+                    status.unwrap_or(StatusFilter::default_for(delta)).filter()
+                }
+                command => panic!("unexpected {:?}", command),
+            }
+        };
+        assert_eq!(filter(&[]), Some("status ne 'completed'"));
+        assert_eq!(filter(&["--delta"]), None);
+        assert_eq!(filter(&["--status", "all"]), None);
+        assert_eq!(filter(&["--status", "open"]), Some("status ne 'completed'"));
+        assert_eq!(
+            filter(&["--status", "completed"]),
+            Some("status eq 'completed'")
+        );
+        assert_eq!(
+            filter(&["--status", "deferred"]),
+            Some("status eq 'deferred'")
+        );
+        assert_eq!(
+            filter(&["--status", "waiting"]),
+            Some("status eq 'waitingOnOthers'")
+        );
+        assert!(Cli::try_parse_from(["mstodo", "tasks", "--status", "done"]).is_err());
+    }
+
+    #[test]
+    fn cancelled_login_exits_with_130() {
+        assert_eq!(report(&AuthenticationError::Cancelled.into()), 130);
+        assert_eq!(report(&AuthenticationError::AuthenticationFailed.into()), 1);
+        assert_eq!(
+            report(&Error::NoMatch("no task list named x".to_owned())),
+            1
+        );
+    }
+
+    fn config() -> Config {
+        Config::parse(
+            r#"
+            output = "json"
+            profile = "work"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn flags_take_precedence_over_the_config() {
+        let cli =
+            Cli::try_parse_from(["mstodo", "--output", "plain", "--profile", "home", "lists"])
+                .unwrap()
+                .with_config(config());
+        assert_eq!(cli.output, Some(OutputFormat::Plain));
+        assert_eq!(cli.profile.as_deref(), Some("home"));
+    }
+
+    #[test]
+    fn config_fills_the_unset_flags() {
+        let cli = Cli::try_parse_from(["mstodo", "lists"])
+            .unwrap()
+            .with_config(config());
+        assert_eq!(cli.output, Some(OutputFormat::Json));
+        assert_eq!(cli.profile.as_deref(), Some("work"));
+    }
+
+    const TASKS_PATH: &str = "/v1.0/me/todo/lists/list/tasks";
+
+    fn bad_request() -> wiremock::ResponseTemplate {
+        wiremock::ResponseTemplate::new(400).set_body_json(serde_json::json!({
+            "error": { "code": "invalidRequest", "message": "Invalid request" }
+        }))
+    }
+
+    async fn two_tasks(server: &wiremock::MockServer) {
+        wiremock::Mock::given(wiremock::matchers::method("GET"))
+            .and(wiremock::matchers::path(TASKS_PATH))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "value": [
+                        testing::task_json("t1", "Buy milk", "notStarted"),
+                        testing::task_json("t2", "Pay rent", "notStarted"),
+                    ]
+                })),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn done_fails_when_a_task_is_not_completed() {
+        let server = wiremock::MockServer::start().await;
+        two_tasks(&server).await;
+        wiremock::Mock::given(wiremock::matchers::method("PATCH"))
+            .and(wiremock::matchers::path(format!("{}/t1", TASKS_PATH)))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(testing::task_json(
+                    "t1",
+                    "Buy milk",
+                    "completed",
+                )),
+            )
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(wiremock::matchers::method("PATCH"))
+            .and(wiremock::matchers::path(format!("{}/t2", TASKS_PATH)))
+            .respond_with(bad_request())
+            .mount(&server)
+            .await;
+
+        let tasks = ["Buy milk".to_owned(), "t2".to_owned()];
+        let err = complete(&testing::client(&server), "list", &tasks)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::TasksFailed {
+                    failed: 1,
+                    total: 2
+                }
+            ),
+            "{:?}",
+            err
+        );
+        assert_eq!(report(&err), 1);
+    }
+
+    #[tokio::test]
+    async fn dry_run_done_succeeds() {
+        let server = wiremock::MockServer::start().await;
+        two_tasks(&server).await;
+
+        let tasks = ["t1".to_owned(), "t2".to_owned()];
+        complete(&testing::dry_run_client(&server), "list", &tasks)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn import_fails_when_a_task_is_not_created() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::path(TASKS_PATH))
+            .and(wiremock::matchers::body_partial_json(
+                serde_json::json!({ "title": "Pay rent" }),
+            ))
+            .respond_with(bad_request())
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::path(TASKS_PATH))
+            .respond_with(
+                wiremock::ResponseTemplate::new(201).set_body_json(testing::task_json(
+                    "t1",
+                    "Buy milk",
+                    "notStarted",
+                )),
+            )
+            .mount(&server)
+            .await;
+
+        let new_tasks = vec![NewTask::new("Buy milk"), NewTask::new("Pay rent")];
+        let err = import(&testing::client(&server), "list", new_tasks)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::TasksFailed {
+                    failed: 1,
+                    total: 2
+                }
+            ),
+            "{:?}",
+            err
+        );
+
+        let new_tasks = vec![NewTask::new("Pay rent")];
+        import(&testing::dry_run_client(&server), "list", new_tasks)
+            .await
+            .unwrap();
+    }
+}
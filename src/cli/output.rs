@@ -0,0 +1,399 @@
+//! Rendering of command results for `--output`
+use std::{io::IsTerminal, time::SystemTime};
+
+use clap::ValueEnum;
+use mstodo_lib::{
+    error::Error,
+    models::{Importance, TaskStatus, TodoTask, TodoTaskList, User},
+};
+use nu_ansi_term::{Color, Style};
+
+/// Value of the `--output` flag
+#[derive(ValueEnum, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The models serialized as JSON
+    Json,
+    /// Aligned columns with a header
+    #[default]
+    Table,
+    /// One item per line
+    Plain,
+    /// One JSON object per line, `tasks` printing each task as soon as it is fetched
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// `color` only matters for [OutputFormat::Table]
+    pub fn renderer(self, color: bool) -> Box<dyn Renderer> {
+        match self {
+            Self::Json => Box::new(JsonRenderer),
+            Self::Table => Box::new(TableRenderer { color }),
+            Self::Plain => Box::new(PlainRenderer),
+            Self::Ndjson => Box::new(NdjsonRenderer),
+        }
+    }
+}
+
+/// Whether output may be styled: not disabled with `--no-color` or `NO_COLOR`,
+/// and going to a terminal rather than a pipe.
+/// See "https://no-color.org" for more information
+pub fn use_color(no_color: bool) -> bool {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && !no_color_env && std::io::stdout().is_terminal()
+}
+
+/// Turns command results into the text printed to stdout
+pub trait Renderer {
+    fn render_lists(&self, lists: &[TodoTaskList]) -> Result<String, Error>;
+    fn render_tasks(&self, tasks: &[TodoTask]) -> Result<String, Error>;
+    /// Tasks along with the list they belong to
+    fn render_search(&self, hits: &[(TodoTaskList, TodoTask)]) -> Result<String, Error>;
+    fn render_user(&self, user: &User) -> Result<String, Error>;
+}
+
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn render_lists(&self, lists: &[TodoTaskList]) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(lists)?)
+    }
+
+    fn render_tasks(&self, tasks: &[TodoTask]) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(tasks)?)
+    }
+
+    fn render_search(&self, hits: &[(TodoTaskList, TodoTask)]) -> Result<String, Error> {
+        let hits: Vec<_> = hits
+            .iter()
+            .map(|(list, task)| serde_json::json!({ "list": list, "task": task }))
+            .collect();
+        Ok(serde_json::to_string_pretty(&hits)?)
+    }
+
+    fn render_user(&self, user: &User) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(user)?)
+    }
+}
+
+/// Aligned columns, completed tasks being dimmed, important ones in bold red
+/// and overdue due dates in red when `color` is set
+pub struct TableRenderer {
+    pub color: bool,
+}
+
+impl TableRenderer {
+    /// Style of the cell of `task` in the column named `header`
+    fn task_style(&self, task: &TodoTask, header: &str, today: &str) -> Style {
+        if !self.color {
+            return Style::default();
+        }
+        if task.status == TaskStatus::Completed {
+            return Style::new().dimmed();
+        }
+        let overdue = task
+            .due_date_time
+            .as_ref()
+            .is_some_and(|due| due.date_time.get(..10).is_some_and(|day| day < today));
+        match header {
+            "IMPORTANCE" | "TITLE" if task.importance == Importance::High => Color::Red.bold(),
+            "DUE" if overdue => Color::Red.normal(),
+            _ => Style::default(),
+        }
+    }
+}
+
+impl Renderer for TableRenderer {
+    fn render_lists(&self, lists: &[TodoTaskList]) -> Result<String, Error> {
+        let rows = lists
+            .iter()
+            .map(|list| vec![list.id.clone(), list.display_name.clone()])
+            .collect();
+        Ok(table(&["ID", "NAME"], rows, |_, _| Style::default()))
+    }
+
+    fn render_tasks(&self, tasks: &[TodoTask]) -> Result<String, Error> {
+        let today = &mstodo_lib::utils::format_utc(SystemTime::now())[..10];
+        let headers = TodoTask::TABLE_HEADERS;
+        let rows = tasks
+            .iter()
+            .map(|task| task.to_table_row().into())
+            .collect();
+        Ok(table(&headers, rows, |row, column| {
+            self.task_style(&tasks[row], headers[column], today)
+        }))
+    }
+
+    fn render_search(&self, hits: &[(TodoTaskList, TodoTask)]) -> Result<String, Error> {
+        let headers: Vec<&str> = std::iter::once("LIST")
+            .chain(TodoTask::TABLE_HEADERS)
+            .collect();
+        let today = &mstodo_lib::utils::format_utc(SystemTime::now())[..10];
+        let rows = hits
+            .iter()
+            .map(|(list, task)| {
+                std::iter::once(list.display_name.clone())
+                    .chain(task.to_table_row())
+                    .collect()
+            })
+            .collect();
+        Ok(table(&headers, rows, |row, column| {
+            self.task_style(&hits[row].1, headers[column], today)
+        }))
+    }
+
+    fn render_user(&self, user: &User) -> Result<String, Error> {
+        let row = vec![
+            user.id.clone(),
+            user.user_principal_name.clone(),
+            user.display_name.clone().unwrap_or_default(),
+        ];
+        Ok(table(&["ID", "USERNAME", "NAME"], vec![row], |_, _| {
+            Style::default()
+        }))
+    }
+}
+
+pub struct NdjsonRenderer;
+
+impl NdjsonRenderer {
+    /// A single line, so tasks can be printed as they arrive
+    pub fn render_task(task: &TodoTask) -> Result<String, Error> {
+        Ok(serde_json::to_string(task)?)
+    }
+}
+
+impl Renderer for NdjsonRenderer {
+    fn render_lists(&self, lists: &[TodoTaskList]) -> Result<String, Error> {
+        json_lines(lists)
+    }
+
+    fn render_tasks(&self, tasks: &[TodoTask]) -> Result<String, Error> {
+        json_lines(tasks)
+    }
+
+    fn render_search(&self, hits: &[(TodoTaskList, TodoTask)]) -> Result<String, Error> {
+        let hits: Vec<_> = hits
+            .iter()
+            .map(|(list, task)| serde_json::json!({ "list": list, "task": task }))
+            .collect();
+        json_lines(&hits)
+    }
+
+    fn render_user(&self, user: &User) -> Result<String, Error> {
+        Ok(serde_json::to_string(user)?)
+    }
+}
+
+pub struct PlainRenderer;
+
+impl Renderer for PlainRenderer {
+    fn render_lists(&self, lists: &[TodoTaskList]) -> Result<String, Error> {
+        Ok(lines(lists.iter().map(|list| list.display_name.clone())))
+    }
+
+    fn render_tasks(&self, tasks: &[TodoTask]) -> Result<String, Error> {
+        Ok(lines(tasks.iter().map(|task| task.to_string())))
+    }
+
+    fn render_search(&self, hits: &[(TodoTaskList, TodoTask)]) -> Result<String, Error> {
+        Ok(lines(hits.iter().map(|(list, task)| {
+            format!("{}: {}", list.display_name, task)
+        })))
+    }
+
+    fn render_user(&self, user: &User) -> Result<String, Error> {
+        Ok(match &user.display_name {
+            Some(name) => format!("{} <{}>", name, user.user_principal_name),
+            None => user.user_principal_name.clone(),
+        })
+    }
+}
+
+fn json_lines<T: serde::Serialize>(items: &[T]) -> Result<String, Error> {
+    let items = items
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(items.join("\n"))
+}
+
+fn lines(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join("\n")
+}
+
+/// Pads every column but the last to its widest cell,
+/// then applies `style(row, column)` to every cell but the headers
+fn table(
+    headers: &[&str],
+    rows: Vec<Vec<String>>,
+    style: impl Fn(usize, usize) -> Style,
+) -> String {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let headers = headers.iter().map(|header| header.to_string()).collect();
+    lines(
+        std::iter::once(headers)
+            .chain(rows)
+            .enumerate()
+            .map(|(row_index, row)| {
+                let last = row.len().saturating_sub(1);
+                row.iter()
+                    .enumerate()
+                    .map(|(i, cell)| {
+                        // padding first so escape sequences do not count towards the width
+                        let cell = if i == last {
+                            cell.clone()
+                        } else {
+                            format!("{:<width$}", cell, width = widths[i])
+                        };
+                        match row_index.checked_sub(1) {
+                            Some(row) => style(row, i).paint(cell).to_string(),
+                            None => cell,
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::{testing, Cli};
+
+    fn tasks() -> Vec<TodoTask> {
+        let mut rent = testing::task_json("t2", "Pay rent", "notStarted");
+        rent["importance"] = "high".into();
+        rent["dueDateTime"] = serde_json::json!({
+            "dateTime": "2024-05-01T00:00:00.0000000",
+            "timeZone": "UTC",
+        });
+        serde_json::from_value(serde_json::json!([
+            testing::task_json("t1", "Buy milk", "completed"),
+            rent,
+            testing::task_json("t3", "Call mum", "notStarted"),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn plain_tasks() {
+        let output = OutputFormat::Plain
+            .renderer(false)
+            .render_tasks(&tasks())
+            .unwrap();
+        assert_eq!(
+            output,
+            "[x] Buy milk\n[ ] Pay rent (due 2024-05-01, high)\n[ ] Call mum"
+        );
+    }
+
+    #[test]
+    fn json_tasks() {
+        let tasks = tasks();
+        let output = OutputFormat::Json
+            .renderer(false)
+            .render_tasks(&tasks)
+            .unwrap();
+        let rendered: Vec<TodoTask> = serde_json::from_str(&output).unwrap();
+        assert_eq!(rendered, tasks);
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value[1]["title"], "Pay rent");
+        assert_eq!(value[1]["importance"], "high");
+        assert_eq!(
+            value[1]["dueDateTime"]["dateTime"],
+            "2024-05-01T00:00:00.0000000"
+        );
+    }
+
+    #[test]
+    fn ndjson_lines_parse_on_their_own() {
+        let mut tasks = tasks();
+        tasks[2].title = "Call mum\nand dad".to_owned();
+        let output = OutputFormat::Ndjson
+            .renderer(false)
+            .render_tasks(&tasks)
+            .unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), tasks.len(), "{}", output);
+        for (line, task) in lines.iter().zip(&tasks) {
+            let rendered: TodoTask = serde_json::from_str(line).unwrap();
+            assert_eq!(&rendered, task);
+            assert_eq!(*line, NdjsonRenderer::render_task(task).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn whoami_output() {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/v1.0/me"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "@odata.context": "https://graph.microsoft.com/v1.0/$metadata#users/$entity",
+                    "businessPhones": [],
+                    "displayName": "Ada Lovelace",
+                    "givenName": "Ada",
+                    "mail": null,
+                    "userPrincipalName": "ada@example.com",
+                    "id": "user",
+                })),
+            )
+            .mount(&server)
+            .await;
+        let user = testing::client(&server).me().await.unwrap();
+        assert_eq!(user.display_name.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(user.mail, None);
+
+        let render = |format: OutputFormat| format.renderer(false).render_user(&user).unwrap();
+        assert_eq!(
+            render(OutputFormat::Plain),
+            "Ada Lovelace <ada@example.com>"
+        );
+        assert_eq!(
+            render(OutputFormat::Table),
+            "ID    USERNAME         NAME\nuser  ada@example.com  Ada Lovelace"
+        );
+        let anonymous = User {
+            display_name: None,
+            ..user
+        };
+        assert_eq!(
+            OutputFormat::Plain
+                .renderer(false)
+                .render_user(&anonymous)
+                .unwrap(),
+            "ada@example.com"
+        );
+    }
+
+    #[test]
+    fn no_color_table_has_no_escape_sequences() {
+        let cli = Cli::try_parse_from(["mstodo", "--no-color", "tasks", "default"]).unwrap();
+        assert!(!use_color(cli.no_color));
+        let tasks = tasks();
+        let output = OutputFormat::Table
+            .renderer(use_color(cli.no_color))
+            .render_tasks(&tasks)
+            .unwrap();
+        assert!(!output.contains('\u{1b}'), "{:?}", output);
+        assert!(output.contains("Pay rent"));
+
+        // the same tasks are styled when colors are enabled
+        let styled = OutputFormat::Table
+            .renderer(true)
+            .render_tasks(&tasks)
+            .unwrap();
+        assert!(styled.contains('\u{1b}'));
+    }
+}
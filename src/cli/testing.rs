@@ -0,0 +1,52 @@
+//! Helpers shared by the tests of the command line, pointing clients at a mock server
+use mstodo_lib::{
+    auth::{responses::AuthenticationResponse, DeviceCodeAuthentication, TokenProvider},
+    prelude::TodoClient,
+};
+use serde_json::json;
+use wiremock::MockServer;
+
+/// Client sending its requests to `server`, with a token that is never refreshed
+pub fn client(server: &MockServer) -> TodoClient {
+    TodoClient::builder()
+        .graph_base_url(&server.uri())
+        .build(token_provider(server))
+}
+
+/// Same as [client] in dry-run mode, sending nothing but reads
+pub fn dry_run_client(server: &MockServer) -> TodoClient {
+    TodoClient::builder()
+        .graph_base_url(&server.uri())
+        .dry_run(true)
+        .build(token_provider(server))
+}
+
+fn token_provider(server: &MockServer) -> TokenProvider {
+    let auth: AuthenticationResponse = serde_json::from_value(json!({
+        "token_type": "Bearer",
+        "scope": "Tasks.ReadWrite",
+        "expires_in": 3600,
+        "ext_expires_in": 3600,
+        "access_token": "access",
+        "refresh_token": "refresh",
+    }))
+    .unwrap();
+    let authenticator = DeviceCodeAuthentication::builder()
+        .authority_host(&server.uri())
+        .build()
+        .unwrap();
+    TokenProvider::new(auth, authenticator)
+}
+
+/// Minimal task as returned by Graph
+pub fn task_json(id: &str, title: &str, status: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "title": title,
+        "status": status,
+        "importance": "normal",
+        "isReminderOn": false,
+        "createdDateTime": "2024-05-01T00:00:00Z",
+        "lastModifiedDateTime": "2024-05-01T00:00:00Z",
+    })
+}
@@ -1,8 +1,33 @@
-use mstodo_lib::auth;
+mod cli;
+
+use clap::Parser;
+use tracing::Level;
+use tracing_subscriber::{filter::filter_fn, prelude::*};
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let authenticator = auth::DeviceCodeAuthentication::new();
-    let res = authenticator.authenticate().await.unwrap();
-    // println!("{}", to_string_pretty(&auth_err).unwrap());
-    println!("{:?}", res);
+    let cli = cli::Cli::parse();
+    // failures are already printed below, their ids only matter with --verbose
+    let verbose = cli.verbose;
+    let filter = filter_fn(move |meta| {
+        if verbose {
+            *meta.level() <= Level::DEBUG
+        } else {
+            *meta.level() <= Level::INFO && *meta.level() != Level::ERROR
+        }
+    });
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(filter),
+        )
+        .init();
+    let result = match cli::Config::load() {
+        Ok(config) => cli.with_config(config).run().await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        std::process::exit(cli::report(&err));
+    }
 }
@@ -0,0 +1,38 @@
+//! Runs the `mstodo` binary, checking the arguments are parsed before anything is sent
+use std::process::{Command, Output};
+
+fn mstodo(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mstodo"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn help_lists_the_subcommands() {
+    let output = mstodo(&["--help"]);
+    assert!(output.status.success());
+    let help = String::from_utf8(output.stdout).unwrap();
+    for command in ["login", "logout", "lists", "tasks"] {
+        assert!(help.contains(command), "{} missing from\n{}", command, help);
+    }
+}
+
+#[test]
+fn subcommand_help() {
+    let output = mstodo(&["tasks", "--help"]);
+    assert!(output.status.success());
+    let help = String::from_utf8(output.stdout).unwrap();
+    assert!(help.contains("[LIST]"), "{}", help);
+    assert!(help.contains("--status"), "{}", help);
+    assert!(help.contains("--delta"), "{}", help);
+}
+
+#[test]
+fn unknown_subcommand_is_a_usage_error() {
+    let output = mstodo(&["frobnicate"]);
+    assert_eq!(output.status.code(), Some(2));
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert!(error.contains("frobnicate"), "{}", error);
+}